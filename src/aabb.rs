@@ -0,0 +1,116 @@
+use crate::{
+    ray::Ray,
+    utils::{self, Interval},
+    vec::{Vec3, VecTypes},
+};
+
+// axis aligned bounding box, one Interval by axis
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        let mut b = Aabb { x, y, z };
+        b.pad_to_minimums();
+        b
+    }
+
+    // box with a and b as extremes, dont need to be in order
+    pub fn from_points(a: Vec3, b: Vec3) -> Self {
+        Aabb::new(
+            Interval::new(a[0].min(b[0]), a[0].max(b[0])),
+            Interval::new(a[1].min(b[1]), a[1].max(b[1])),
+            Interval::new(a[2].min(b[2]), a[2].max(b[2])),
+        )
+    }
+
+    pub fn union(a: &Aabb, b: &Aabb) -> Self {
+        Aabb {
+            x: Interval::union(&a.x, &b.x),
+            y: Interval::union(&a.y, &b.y),
+            z: Interval::union(&a.z, &b.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn min(&self) -> Vec3 {
+        Vec3::new(VecTypes::Coordinates, self.x.min, self.y.min, self.z.min)
+    }
+
+    pub fn max(&self) -> Vec3 {
+        Vec3::new(VecTypes::Coordinates, self.x.max, self.y.max, self.z.max)
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min() + self.max()).mul(0.5)
+    }
+
+    // the 8 corners, used to transform the box
+    pub fn corners(&self) -> [Vec3; 8] {
+        let mut c = [Vec3::default(); 8];
+        for (i, p) in c.iter_mut().enumerate() {
+            *p = Vec3::new(
+                VecTypes::Coordinates,
+                if i & 1 == 0 { self.x.min } else { self.x.max },
+                if i & 2 == 0 { self.y.min } else { self.y.max },
+                if i & 4 == 0 { self.z.min } else { self.z.max },
+            );
+        }
+        c
+    }
+
+    // return the (t_enter, t_exit) of the box clipped to [r_min, r_max]
+    pub fn hit_interval(&self, r: &Ray, r_max: f64, r_min: f64) -> Option<Interval> {
+        let mut t = Interval::new(r_min, r_max);
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / r.direction[axis];
+
+            let t0 = (ax.min - r.origin[axis]) * adinv;
+            let t1 = (ax.max - r.origin[axis]) * adinv;
+
+            t.min = t.min.max(t0.min(t1));
+            t.max = t.max.min(t0.max(t1));
+            if t.max <= t.min {
+                return None;
+            }
+        }
+        Some(t)
+    }
+
+    // avoid boxes with zero width in some axis, like a flat quad
+    fn pad_to_minimums(&mut self) {
+        let delta = 0.0001;
+        if self.x.size() < delta {
+            self.x = self.x.expand(delta);
+        }
+        if self.y.size() < delta {
+            self.y = self.y.expand(delta);
+        }
+        if self.z.size() < delta {
+            self.z = self.z.expand(delta);
+        }
+    }
+}
+
+impl Default for Aabb {
+    // empty box, any union with it return the other box
+    fn default() -> Self {
+        Aabb {
+            x: utils::EMPTY,
+            y: utils::EMPTY,
+            z: utils::EMPTY,
+        }
+    }
+}
@@ -0,0 +1,660 @@
+use std::sync::Mutex;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    light::LightEmission,
+    ray::{HitRecord, HittableList, Ray},
+    vec::Vec3,
+};
+
+fn is_black(c: &Color) -> bool {
+    c.rgb_array().iter().all(|v| *v == 0.0)
+}
+
+// light that the light paths carry to the camera, they can reach any pixel,
+// so it is summed apart and added to the image after all the samples
+pub struct Splats {
+    wid: u32,
+    pixels: Vec<Mutex<Color>>,
+}
+
+impl Splats {
+    pub fn new(wid: u32, hei: u32) -> Self {
+        Splats {
+            wid,
+            pixels: (0..wid * hei)
+                .map(|_| Mutex::new(Color::default()))
+                .collect(),
+        }
+    }
+
+    pub fn add(&self, x: u32, y: u32, c: Color) {
+        if let Some(p) = self.pixels.get((y * self.wid + x) as usize) {
+            *p.lock().unwrap() += c;
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels
+            .get((y * self.wid + x) as usize)
+            .map(|p| *p.lock().unwrap())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Camera,
+    Light,
+    Surface,
+}
+
+// a point of a subpath, from the camera or from a light
+struct Vertex {
+    kind: Kind,
+    point: Vec3,
+    // None in the camera, in the lights in a point and inside the volumes
+    normal: Option<Vec3>,
+    // hit in the surfaces and volumes, with the ray that arrived in it
+    hit: Option<(HitRecord, Ray)>,
+    // index in the lights, for the lights and for the hits in them
+    light: Option<usize>,
+    // light(or importance) carried from the start of the subpath
+    beta: Color,
+    // scattered by a delta lobe, the MIS skip it
+    delta: bool,
+    // the light is in a point, no path can hit it
+    delta_light: bool,
+    // pdf in area of this vertex be made by its subpath, and by the other
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl Vertex {
+    fn camera(point: Vec3) -> Self {
+        Vertex {
+            kind: Kind::Camera,
+            point,
+            normal: None,
+            hit: None,
+            light: None,
+            beta: Color::new(1.0, 1.0, 1.0),
+            delta: false,
+            delta_light: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn light(id: usize, point: Vec3, normal: Vec3, delta_light: bool, pdf_fwd: f64) -> Self {
+        Vertex {
+            kind: Kind::Light,
+            point,
+            normal: (!normal.near_zero()).then_some(normal),
+            hit: None,
+            light: Some(id),
+            beta: Color::default(),
+            delta: false,
+            delta_light,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn surface(h: HitRecord, r: Ray, beta: Color) -> Self {
+        Vertex {
+            kind: Kind::Surface,
+            point: h.point,
+            normal: (!h.mat.is_medium()).then_some(h.normal),
+            light: h.light,
+            hit: Some((h, r)),
+            beta,
+            delta: false,
+            delta_light: false,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    // a delta material can only be followed, the other ones can be
+    // connected even when the lobe chosen was delta, like in a mix
+    fn connectible(&self) -> bool {
+        match &self.hit {
+            Some((h, _)) => !h.mat.is_delta(h),
+            None => true,
+        }
+    }
+
+    // scattering to the point, with the cos
+    fn eval(&self, to: &Vec3) -> Color {
+        match &self.hit {
+            Some((h, r)) => h.mat.eval(r, h, &(*to - self.point).unit_vec()),
+            None => Color::default(),
+        }
+    }
+}
+
+// pdf in solid angle in from to pdf in area in the vertex to
+fn convert_density(pdf: f64, from: &Vertex, to: &Vertex) -> f64 {
+    let d = to.point - from.point;
+    let dist2 = d.vec_length().powi(2);
+    if dist2 == 0.0 {
+        return 0.0;
+    }
+    let mut pdf = pdf / dist2;
+    if let Some(n) = to.normal {
+        pdf *= n.dot(&d.div(dist2.sqrt())).abs();
+    }
+    pdf
+}
+
+// bidirectional path tracing of one sample of the camera, like in pbrt
+// a subpath from the camera and other from a light, all the ways of join
+// then are weighted by the balance heuristic
+// the environment and the directional lights can't start light paths, they
+// are sampled from the camera path like in the path tracer
+// the materials are taken as symmetric, a bit off with refraction
+pub struct Bdpt<'a> {
+    cam: &'a Camera,
+    world: &'a HittableList,
+    splats: &'a Splats,
+    time: f64,
+}
+
+impl<'a> Bdpt<'a> {
+    pub fn new(cam: &'a Camera, world: &'a HittableList, splats: &'a Splats, time: f64) -> Self {
+        Bdpt {
+            cam,
+            world,
+            splats,
+            time,
+        }
+    }
+
+    // light arriving in the camera by the ray r, the paths that reach
+    // the camera directly go to the splats
+    pub fn li(&self, r: &Ray) -> Color {
+        let mut camera = vec![Vertex::camera(r.origin)];
+        let (_, pdf) = self.cam.importance(&r.direction);
+        let mut l = self.walk(*r, Color::new(1.0, 1.0, 1.0), pdf, true, &mut camera);
+        let light = self.light_path();
+
+        let max = self.cam.max_deep_ray as usize;
+        for t in 1..=camera.len() {
+            for s in 0..=light.len() {
+                if s + t < 2 || s + t - 2 > max || (s == 1 && t == 1) {
+                    continue;
+                }
+                if t == 1 {
+                    self.splat(&light, &camera, s);
+                } else {
+                    l += self.connect(&light, &camera, s, t);
+                }
+            }
+        }
+        l
+    }
+
+    fn light_path(&self) -> Vec<Vertex> {
+        let mut path = Vec::new();
+        let Some((id, light, prob)) = self.cam.lights.choose_emitter() else {
+            return path;
+        };
+        let Some(LightEmission {
+            origin,
+            dir,
+            normal,
+            le,
+            pdf_pos,
+            pdf_dir,
+        }) = light.sample_le()
+        else {
+            return path;
+        };
+        if pdf_pos == 0.0 || pdf_dir == 0.0 || is_black(&le) {
+            return path;
+        }
+        path.push(Vertex::light(
+            id,
+            origin,
+            normal,
+            light.is_delta(),
+            pdf_pos * prob,
+        ));
+        let cos = if normal.near_zero() {
+            1.0
+        } else {
+            normal.dot(&dir).abs()
+        };
+        let beta = le.mul(cos / (prob * pdf_pos * pdf_dir));
+        let r = Ray::new_timed(origin, dir, self.time);
+        self.walk(r, beta, pdf_dir, false, &mut path);
+        path
+    }
+
+    // follow the ray by the materials, adding a vertex by hit
+    // pdf is the pdf in solid angle of the direction of r
+    // the camera paths also get the light of the environment, that is returned
+    fn walk(
+        &self,
+        mut r: Ray,
+        mut beta: Color,
+        mut pdf: f64,
+        camera: bool,
+        path: &mut Vec<Vertex>,
+    ) -> Color {
+        let mut env = Color::default();
+        // pdf of the last bounce for the MIS of the environment, 0.0 in the camera
+        let mut bsdf_pdf = 0.0;
+        for _ in 0..self.cam.max_deep_ray {
+            let mut h = HitRecord::default();
+            if !self.cam.hit_world(&r, self.world, &mut h) {
+                if camera {
+                    env += beta * self.cam.escaped(&r, bsdf_pdf);
+                }
+                break;
+            }
+            if camera && let Some((f, l)) = self.cam.sample_environment(&r, &h, self.world) {
+                env += beta * f * l;
+            }
+
+            let mut next = Ray::default();
+            let mut attenuation = Color::default();
+            let scattered = h.mat.scatter(&r, &mut next, &h, &mut attenuation);
+            let (pdf_next, pdf_back) = match scattered {
+                Some(pdf) => {
+                    let wi = next.direction.unit_vec();
+                    let back = r.spawn(h.point + wi, wi.mul(-1.0));
+                    (pdf, h.mat.pdf(&back, &h, &r.direction.unit_vec().mul(-1.0)))
+                }
+                None => (0.0, 0.0),
+            };
+
+            let mut v = Vertex::surface(h, r, beta);
+            if let Some(prev) = path.last() {
+                v.pdf_fwd = convert_density(pdf, prev, &v);
+            }
+            path.push(v);
+            if scattered.is_none() {
+                break;
+            }
+
+            let n = path.len();
+            // the specular can only be followed, not connected
+            let delta = pdf_next == 0.0;
+            path[n - 1].delta = delta;
+            let pdf_back = if delta { 0.0 } else { pdf_back };
+            path[n - 2].pdf_rev = convert_density(pdf_back, &path[n - 1], &path[n - 2]);
+
+            pdf = pdf_next;
+            bsdf_pdf = pdf_next;
+            beta = beta * attenuation;
+            r = next;
+        }
+        env
+    }
+
+    // light of the strategy with s vertices of the light path and t >= 2
+    // of the camera path
+    fn connect(&self, light: &[Vertex], camera: &[Vertex], s: usize, t: usize) -> Color {
+        let pt = &camera[t - 1];
+        if s == 0 {
+            // the camera path hit a light by itself
+            let Some((h, _)) = &pt.hit else {
+                return Color::default();
+            };
+            let le = h.mat.emitted(h);
+            if is_black(&le) {
+                return Color::default();
+            }
+            // the emissive objects out of the lights have only this way
+            if pt.light.is_none() {
+                return pt.beta * le;
+            }
+            return (pt.beta * le).mul(self.mis_weight(light, camera, None, s, t));
+        }
+        if !pt.connectible() {
+            return Color::default();
+        }
+
+        if s == 1 {
+            return self.connect_light(light, camera, t);
+        }
+
+        let qs = &light[s - 1];
+        if !qs.connectible() {
+            return Color::default();
+        }
+        let f = pt.eval(&qs.point) * qs.eval(&pt.point);
+        if is_black(&f) || !self.visible(pt, qs) {
+            return Color::default();
+        }
+        let dist2 = (qs.point - pt.point).vec_length().powi(2);
+        let l = (pt.beta * f * qs.beta).mul(1.0 / dist2);
+        l.mul(self.mis_weight(light, camera, None, s, t))
+    }
+
+    // s = 1, a new point in a light, choosed from the camera vertex like
+    // the next event estimation of the path tracer
+    fn connect_light(&self, light: &[Vertex], camera: &[Vertex], t: usize) -> Color {
+        let pt = &camera[t - 1];
+        let Some((h, r)) = &pt.hit else {
+            return Color::default();
+        };
+        let Some((id, lt, prob)) = self.cam.lights.choose(&pt.point) else {
+            return Color::default();
+        };
+        let Some(ls) = lt.sample_li(&pt.point) else {
+            return Color::default();
+        };
+        let f = h.mat.eval(r, h, &ls.wi);
+        if is_black(&f) || ls.pdf <= 0.0 {
+            return Color::default();
+        }
+        let shadow = r.spawn(pt.point, ls.wi);
+        let dist = ls.dist * (1.0 - 1e-6);
+        if self
+            .cam
+            .hit_world_until(&shadow, self.world, dist, &mut HitRecord::default())
+        {
+            return Color::default();
+        }
+        let l = (pt.beta * f * ls.li).mul(1.0 / (ls.pdf * prob));
+        // the lights in the infinite are only reached here
+        if lt.bounds().is_none() {
+            return l;
+        }
+        let mut sampled = Vertex::light(
+            id,
+            pt.point + ls.wi.mul(ls.dist),
+            ls.normal,
+            lt.is_delta(),
+            0.0,
+        );
+        sampled.pdf_fwd = self.pdf_light_origin(&sampled);
+        l.mul(self.mis_weight(light, camera, Some(&sampled), 1, t))
+    }
+
+    // t = 1, the vertex s of the light path seen by the camera
+    fn splat(&self, light: &[Vertex], camera: &[Vertex], s: usize) {
+        let qs = &light[s - 1];
+        if !qs.connectible() {
+            return;
+        }
+        let to_qs = qs.point - self.cam.origin();
+        let Some((x, y)) = self.cam.raster(&to_qs) else {
+            return;
+        };
+        // with a crop only its pixels get samples, the splats out of it are
+        // lost, and there are fewer light paths, so the ones inside weigh more
+        let region = self.cam.region();
+        if !region.contains(x, y) {
+            return;
+        }
+        let crop = self.cam.image_area() as f64 / region.area() as f64;
+        // the pdf is the importance with one less cos
+        let (we, pdf) = self.cam.importance(&to_qs);
+        let cos = pdf / we;
+        let f = qs.eval(&self.cam.origin());
+        let sampled = Vertex::camera(self.cam.origin());
+        if is_black(&f) || !self.visible(&sampled, qs) {
+            return;
+        }
+        let dist2 = to_qs.vec_length().powi(2);
+        let l = (qs.beta * f).mul(we * cos / dist2);
+        let w = self.mis_weight(light, camera, Some(&sampled), s, 1);
+        self.splats.add(x, y, l.mul(w * crop));
+    }
+
+    fn visible(&self, a: &Vertex, b: &Vertex) -> bool {
+        let d = b.point - a.point;
+        let dist = d.vec_length();
+        if dist == 0.0 {
+            return false;
+        }
+        let shadow = Ray::new_timed(a.point, d.div(dist), self.time);
+        !self
+            .cam
+            .hit_world_until(&shadow, self.world, dist - 1e-3, &mut HitRecord::default())
+    }
+
+    // pdf in area of v make next, coming from prev
+    fn pdf(&self, v: &Vertex, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        match v.kind {
+            Kind::Light => self.pdf_light(v, next),
+            Kind::Camera => {
+                convert_density(self.cam.importance(&(next.point - v.point)).1, v, next)
+            }
+            Kind::Surface => {
+                let (Some((h, r)), Some(prev)) = (&v.hit, prev) else {
+                    return 0.0;
+                };
+                let r_in = r.spawn(prev.point, (v.point - prev.point).unit_vec());
+                let pdf = h.mat.pdf(&r_in, h, &(next.point - v.point).unit_vec());
+                convert_density(pdf, v, next)
+            }
+        }
+    }
+
+    // pdf in area of the light in v emit to next
+    fn pdf_light(&self, v: &Vertex, next: &Vertex) -> f64 {
+        let Some(light) = v.light.and_then(|id| self.cam.lights.get(id)) else {
+            return 0.0;
+        };
+        let (_, pdf_dir) = light.pdf_le(&v.point, &(next.point - v.point).unit_vec());
+        convert_density(pdf_dir, v, next)
+    }
+
+    // pdf in area of a light path start in v
+    fn pdf_light_origin(&self, v: &Vertex) -> f64 {
+        let Some(id) = v.light else {
+            return 0.0;
+        };
+        let Some(light) = self.cam.lights.get(id) else {
+            return 0.0;
+        };
+        let (pdf_pos, _) = light.pdf_le(&v.point, &Vec3::default());
+        pdf_pos * self.cam.lights.emitter_pmf(id)
+    }
+
+    // balance heuristic of the strategy against all the others that could
+    // make the same path, by the ratios of the pdfs in each vertex
+    // sampled is the new vertex of the strategies s = 1 and t = 1
+    fn mis_weight(
+        &self,
+        light: &[Vertex],
+        camera: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+    ) -> f64 {
+        if s + t == 2 {
+            return 1.0;
+        }
+        // (pdf_fwd, pdf_rev, delta) of the vertices of the path
+        let mut cam: Vec<(f64, f64, bool)> = camera[..t]
+            .iter()
+            .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+            .collect();
+        let mut lig: Vec<(f64, f64, bool)> = light[..s]
+            .iter()
+            .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+            .collect();
+
+        let (pt, qs) = match (s, t, sampled) {
+            (_, 1, Some(v)) => (v, (s > 0).then(|| &light[s - 1])),
+            (1, _, Some(v)) => (&camera[t - 1], Some(v)),
+            _ => (&camera[t - 1], (s > 0).then(|| &light[s - 1])),
+        };
+        if let Some(v) = sampled {
+            let entry = (v.pdf_fwd, v.pdf_rev, v.delta);
+            if t == 1 {
+                cam[0] = entry;
+            } else {
+                lig[0] = entry;
+            }
+        }
+        let pt_minus = (t > 1).then(|| &camera[t - 2]);
+        let qs_minus = (s > 1).then(|| &light[s - 2]);
+
+        // the vertices of the connection, with the pdfs in the other way
+        cam[t - 1].2 = false;
+        cam[t - 1].1 = match (qs, pt_minus) {
+            (Some(qs), _) => self.pdf(qs, qs_minus, pt),
+            (None, Some(_)) => self.pdf_light_origin(pt),
+            (None, None) => 0.0,
+        };
+        if let Some(ptm) = pt_minus {
+            cam[t - 2].1 = match qs {
+                Some(qs) => self.pdf(pt, Some(qs), ptm),
+                None => self.pdf_light(pt, ptm),
+            };
+        }
+        if let Some(qs) = qs {
+            lig[s - 1].2 = false;
+            lig[s - 1].1 = self.pdf(pt, pt_minus, qs);
+            if let Some(qsm) = qs_minus {
+                lig[s - 2].1 = self.pdf(qs, Some(pt), qsm);
+            }
+        }
+
+        // zero is a delta, that don't change the ratio
+        let remap = |f: f64| if f != 0.0 { f } else { 1.0 };
+        let mut sum = 0.0;
+        let mut ri = 1.0;
+        for i in (1..t).rev() {
+            ri *= remap(cam[i].1) / remap(cam[i].0);
+            if !cam[i].2 && !cam[i - 1].2 {
+                sum += ri;
+            }
+        }
+        let delta_light = match (s, sampled) {
+            (1, Some(v)) => v.delta_light,
+            _ => light.first().is_some_and(|v| v.delta_light),
+        };
+        ri = 1.0;
+        for i in (0..s).rev() {
+            ri *= remap(lig[i].1) / remap(lig[i].0);
+            let before_delta = if i > 0 { lig[i - 1].2 } else { delta_light };
+            if !before_delta && !lig[i].2 {
+                sum += ri;
+            }
+        }
+        1.0 / (1.0 + sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        camera::{Camera, Integrator},
+        color::Color,
+        environment::Environment,
+        film::Progressive,
+        material::Lambertian,
+        quad::Quad,
+        ray::HittableList,
+        vec::{Vec3, VecTypes},
+    };
+
+    // no light out of the box, only the light of the scene
+    struct Black;
+
+    impl Environment for Black {
+        fn radiance(&self, _dir: &Vec3) -> Color {
+            Color::default()
+        }
+        fn sample(&self) -> Option<(Vec3, Color, f64)> {
+            None
+        }
+        fn pdf(&self, _dir: &Vec3) -> f64 {
+            0.0
+        }
+    }
+
+    fn p(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3::new(VecTypes::Coordinates, x, y, z)
+    }
+
+    // mean of the linear radiance of a diffuse cornell box, open in the front
+    fn cornell_mean(integrator: Integrator) -> f64 {
+        let mut world = HittableList::new();
+        let white = || Box::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+        let red = Box::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+        let green = Box::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+        world.add(Box::new(Quad::new(
+            p(1.0, 0.0, 0.0),
+            p(0.0, 2.0, 0.0),
+            p(0.0, 0.0, -2.0),
+            green,
+        )));
+        world.add(Box::new(Quad::new(
+            p(-1.0, 0.0, 0.0),
+            p(0.0, 2.0, 0.0),
+            p(0.0, 0.0, -2.0),
+            red,
+        )));
+        world.add(Box::new(Quad::new(
+            p(-1.0, 0.0, 0.0),
+            p(2.0, 0.0, 0.0),
+            p(0.0, 0.0, -2.0),
+            white(),
+        )));
+        world.add(Box::new(Quad::new(
+            p(-1.0, 2.0, 0.0),
+            p(2.0, 0.0, 0.0),
+            p(0.0, 0.0, -2.0),
+            white(),
+        )));
+        world.add(Box::new(Quad::new(
+            p(-1.0, 0.0, -2.0),
+            p(2.0, 0.0, 0.0),
+            p(0.0, 2.0, 0.0),
+            white(),
+        )));
+
+        let mut c = Camera::new(1.0, 16);
+        c.lookfrom = p(0.0, 1.0, 3.0);
+        c.lookat = p(0.0, 1.0, 0.0);
+        c.vfov = 40.0;
+        c.max_deep_ray = 5;
+        c.integrator = integrator;
+        c.environment = Some(Arc::new(Black));
+        c.lights.add_sphere(
+            &mut world,
+            p(0.0, 1.7, -1.0),
+            0.15,
+            Color::new(20.0, 20.0, 20.0),
+        );
+
+        let mut mean = 0.0;
+        c.render_progressive(&world, Progressive::new(256), |progress, film| {
+            if progress.samples == 256 {
+                let (wid, hei) = (film.width(), film.height());
+                for y in 0..hei {
+                    for x in 0..wid {
+                        let [r, g, b] = film.color(x, y).rgb_array();
+                        mean += (r + g + b) / 3.0;
+                    }
+                }
+                mean /= (wid * hei) as f64;
+            }
+            true
+        });
+        mean
+    }
+
+    #[test]
+    fn bidirectional_agrees_with_path_tracer() {
+        let pt = cornell_mean(Integrator::PathTracer);
+        let bdpt = cornell_mean(Integrator::Bidirectional);
+        assert!(pt > 0.0);
+        assert!(
+            (pt - bdpt).abs() / pt < 0.05,
+            "path tracer {pt}, bidirectional {bdpt}"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::{path::Path, sync::Arc};
+
+use image::ImageResult;
+
+use crate::{
+    color::Color,
+    material::Material,
+    onb::Onb,
+    ray::{HitRecord, Ray},
+    texture::{ImageTexture, TextureRef},
+    vec::Vec3,
+};
+
+// the shading normal can't be seen from behind by the ray, or the materials
+// sample directions inside the surface, so bend it to the ray if needed
+const MIN_COS: f64 = 0.01;
+
+// materials that change the normal of the inner material in each point
+trait Perturb {
+    fn inner(&self) -> &dyn Material;
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3;
+
+    // the hit seen by the inner material, with the perturbed normal
+    fn shading_record(&self, r_in: &Ray, rec: &HitRecord) -> HitRecord {
+        let wo = r_in.direction.unit_vec().mul(-1.0);
+        let mut n = self.shading_normal(rec);
+        if n.dot(&rec.normal) <= 0.0 {
+            n = rec.normal;
+        }
+        let cos = wo.dot(&n);
+        if cos < MIN_COS {
+            n = (n + wo.mul(MIN_COS - cos)).unit_vec();
+        }
+        let mut s = rec.clone();
+        s.normal = n;
+        s
+    }
+}
+
+// the direction need to be in the same side for the geometric and the shading
+// normals, else the light leak through the surface or a reflection go inside
+fn same_side(d: &Vec3, rec: &HitRecord, shading: &HitRecord) -> bool {
+    d.dot(&rec.normal) * d.dot(&shading.normal) > 0.0
+}
+
+fn reflect_perturbed<P: Perturb>(
+    p: &P,
+    r_in: &Ray,
+    r_ref: &mut Ray,
+    rec: &HitRecord,
+    attenuation: &mut Color,
+) -> bool {
+    let s = p.shading_record(r_in, rec);
+    p.inner().reflect(r_in, r_ref, &s, attenuation) && same_side(&r_ref.direction, rec, &s)
+}
+
+// the pdf of the inner material, that know the lobe it chose
+fn scatter_perturbed<P: Perturb>(
+    p: &P,
+    r_in: &Ray,
+    r_ref: &mut Ray,
+    rec: &HitRecord,
+    attenuation: &mut Color,
+) -> Option<f64> {
+    let s = p.shading_record(r_in, rec);
+    let pdf = p.inner().scatter(r_in, r_ref, &s, attenuation)?;
+    same_side(&r_ref.direction, rec, &s).then_some(pdf)
+}
+
+fn eval_perturbed<P: Perturb>(p: &P, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+    let s = p.shading_record(r_in, rec);
+    if !same_side(wi, rec, &s) {
+        return Color::default();
+    }
+    p.inner().eval(r_in, &s, wi)
+}
+
+fn pdf_perturbed<P: Perturb>(p: &P, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+    let s = p.shading_record(r_in, rec);
+    if !same_side(wi, rec, &s) {
+        return 0.0;
+    }
+    p.inner().pdf(r_in, &s, wi)
+}
+
+// unit tangent and bitangent in the plane of the normal, following the uvs
+// or any pair if the object have no uvs
+fn tangent_frame(rec: &HitRecord) -> (Vec3, Vec3) {
+    let n = rec.normal;
+    let t = rec.tangent - n.mul(n.dot(&rec.tangent));
+    if t.near_zero() {
+        let frame = Onb::new(&n);
+        return (frame.u, frame.v);
+    }
+    let t = t.unit_vec();
+    let b = n * t;
+    // the normal is flipped in the back face, keep the b with the dp/dv
+    if b.dot(&rec.bitangent) < 0.0 {
+        (t, b.mul(-1.0))
+    } else {
+        (t, b)
+    }
+}
+
+// normal map in the tangent space, the usual blue images, where the rgb
+// in [0, 1] is the normal in [-1, 1] and the blue is the geometric normal
+// the pixels are read without the gamma decoding of the color textures
+pub struct NormalMap {
+    inner: Box<dyn Material>,
+    map: Arc<ImageTexture>,
+    strength: f64,
+}
+
+impl NormalMap {
+    pub fn new(inner: Box<dyn Material>, map: Arc<ImageTexture>) -> Self {
+        Self {
+            inner,
+            map,
+            strength: 1.0,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(inner: Box<dyn Material>, path: P) -> ImageResult<Self> {
+        Ok(Self::new(inner, Arc::new(ImageTexture::load(path)?)))
+    }
+
+    // 0.0 is flat, 1.0 the normals of the image, more exaggerate it
+    pub fn with_strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+}
+
+impl Perturb for NormalMap {
+    fn inner(&self) -> &dyn Material {
+        self.inner.as_ref()
+    }
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        let c = self.map.raw(rec.u, rec.v);
+        let x = (2.0 * c.rgb[0] - 1.0) * self.strength;
+        let y = (2.0 * c.rgb[1] - 1.0) * self.strength;
+        let z = (2.0 * c.rgb[2] - 1.0).max(0.0);
+        let (t, b) = tangent_frame(rec);
+        (t.mul(x) + b.mul(y) + rec.normal.mul(z)).unit_vec()
+    }
+}
+
+impl Material for NormalMap {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            inner: self.inner.clone_box(),
+            map: self.map.clone(),
+            strength: self.strength,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        reflect_perturbed(self, r_in, r_ref, rec, attenuation)
+    }
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> Option<f64> {
+        scatter_perturbed(self, r_in, r_ref, rec, attenuation)
+    }
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.inner.emitted(rec)
+    }
+    fn is_dispersive(&self) -> bool {
+        self.inner.is_dispersive()
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        eval_perturbed(self, r_in, rec, wi)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        pdf_perturbed(self, r_in, rec, wi)
+    }
+    fn is_delta(&self, rec: &HitRecord) -> bool {
+        self.inner.is_delta(rec)
+    }
+}
+
+// bump from the height of any scalar texture, the surface is moved along the
+// normal by height * scale, in the units of the scene, and the normal is the
+// one of the moved surface, by finite diferences in u and v
+pub struct BumpMap {
+    inner: Box<dyn Material>,
+    height: TextureRef,
+    scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(inner: Box<dyn Material>, height: TextureRef, scale: f64) -> Self {
+        Self {
+            inner,
+            height,
+            scale,
+        }
+    }
+}
+
+impl Perturb for BumpMap {
+    fn inner(&self) -> &dyn Material {
+        self.inner.as_ref()
+    }
+    fn shading_normal(&self, rec: &HitRecord) -> Vec3 {
+        let n = rec.normal;
+        let (dpdu, dpdv) = if rec.tangent.near_zero() || rec.bitangent.near_zero() {
+            tangent_frame(rec)
+        } else {
+            (rec.tangent, rec.bitangent)
+        };
+
+        let h = |u: f64, v: f64, p: &Vec3| self.scale * self.height.scalar(u, v, p);
+        let du = 1e-3;
+        let dv = 1e-3;
+        let h0 = h(rec.u, rec.v, &rec.point);
+        let dhdu = (h(rec.u + du, rec.v, &(rec.point + dpdu.mul(du))) - h0) / du;
+        let dhdv = (h(rec.u, rec.v + dv, &(rec.point + dpdv.mul(dv))) - h0) / dv;
+
+        let bumped = (dpdu + n.mul(dhdu)) * (dpdv + n.mul(dhdv));
+        if bumped.near_zero() {
+            return n;
+        }
+        let bumped = bumped.unit_vec();
+        if bumped.dot(&n) < 0.0 {
+            bumped.mul(-1.0)
+        } else {
+            bumped
+        }
+    }
+}
+
+impl Material for BumpMap {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            inner: self.inner.clone_box(),
+            height: self.height.clone(),
+            scale: self.scale,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        reflect_perturbed(self, r_in, r_ref, rec, attenuation)
+    }
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> Option<f64> {
+        scatter_perturbed(self, r_in, r_ref, rec, attenuation)
+    }
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.inner.emitted(rec)
+    }
+    fn is_dispersive(&self) -> bool {
+        self.inner.is_dispersive()
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        eval_perturbed(self, r_in, rec, wi)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        pdf_perturbed(self, r_in, rec, wi)
+    }
+    fn is_delta(&self, rec: &HitRecord) -> bool {
+        self.inner.is_delta(rec)
+    }
+}
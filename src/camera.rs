@@ -1,13 +1,80 @@
-use image::{ImageBuffer, Rgb, RgbImage};
-use rayon::prelude::*;
+use std::{
+    io,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+use image::RgbImage;
 
 use crate::{
+    bdpt::{Bdpt, Splats},
+    checkpoint::{self, Checkpoint},
     color::Color,
+    environment::Environment,
+    film::{Film, Progress, Progressive},
+    light::LightList,
+    medium::Fog,
+    photon::{PhotonMap, PhotonSettings},
     ray::{HitRecord, Hittable, HittableList, Ray},
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    tile::{self, Region, TileOrder},
     utils::{self, INF, sample_square},
     vec::{Vec3, VecTypes},
 };
 
+// mixed in the seed of the photons, apart from the samples of the pixels
+const PHOTON_STREAM: u64 = u64::MAX;
+
+// how the light is carried by the paths
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    // the path multiply rgb colors
+    #[default]
+    Rgb,
+    // the path carry hero wavelengths, the colors are converted to spectra
+    // and go back to rgb in the film, by the CIE matching functions
+    Spectral,
+}
+
+// how the paths that carry the light are built
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    // paths from the camera, with next event estimation in each bounce
+    #[default]
+    PathTracer,
+    // paths from the camera and from the lights, connected in all the ways
+    // and weighted by MIS, for lights inside shades or behind small openings
+    // only in rgb, the spectral mode is rejected
+    Bidirectional,
+}
+
+// where a camera path is for the photon map, the caustics are the paths
+// from a diffuse hit that cross only specular materials to a light
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CausticPath {
+    No,
+    Diffuse,
+    Specular,
+}
+
+impl CausticPath {
+    // the state after a hit, left by a direction with the pdf
+    fn after(self, h: &HitRecord, pdf: f64) -> Self {
+        if h.mat.is_medium() {
+            CausticPath::No
+        } else if pdf > 0.0 {
+            CausticPath::Diffuse
+        } else if self == CausticPath::No {
+            CausticPath::No
+        } else {
+            CausticPath::Specular
+        }
+    }
+}
+
 // responsible for costruct and dispatch rays into world
 // and use the result to build the image
 #[derive(Default)]
@@ -21,13 +88,48 @@ pub struct Camera {
     pub lookat: Vec3,   // point camera is looking at
     pub vup: Vec3,      // Camera-relative up direction
 
+    // interval of the time [0.0, 1.0] where the shutter is open
+    // each ray get a random time inside it, giving motion blur
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+
+    // fog filling the world, None is clean air
+    pub fog: Option<Fog>,
+
+    // the spectral mode is only for the path tracer, the render panic if
+    // it is used with the bidirectional
+    pub mode: RenderMode,
+
+    pub integrator: Integrator,
+
+    // caustics by photon mapping, only for the path tracer
+    pub photons: Option<PhotonSettings>,
+
+    // light of the infinite, like a HDRI, None is the white to blue gradient
+    // it is sampled as a light in each bounce
+    pub environment: Option<Arc<dyn Environment>>,
+
+    // lights for the next event estimation, the point, spot and directional
+    // are only here, the spheres of add_sphere are also in the world
+    pub lights: LightList,
+
+    // max bounces of a path
+    pub max_deep_ray: u8,
+
+    // each pass is split in tiles of tile_size pixels, done in the order
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    // only the pixels inside are rendered, the others stay black, to give
+    // more samples to a problem area, see Region::paste and the checkpoints
+    pub crop: Option<Region>,
+
     image_hei: u32,
     center: Vec3,
     pixel00_loc: Vec3,
     delta_x: Vec3,
     delta_y: Vec3,
-    pixel_samples_scale: f64,
-    max_deep_ray: u8,
+    // photons of the current pass
+    caustics: Option<PhotonMap>,
 
     // Camera frame relative basis
     u: Vec3, // camera rigth
@@ -36,31 +138,312 @@ pub struct Camera {
 }
 
 impl Camera {
+    // find the closest hit in the world, the fog included
+    pub fn hit_world(&self, r: &Ray, world: &HittableList, h: &mut HitRecord) -> bool {
+        self.hit_world_until(r, world, INF, h)
+    }
+
+    // same of hit_world, only before the t_max, for the shadow rays
+    pub fn hit_world_until(
+        &self,
+        r: &Ray,
+        world: &HittableList,
+        t_max: f64,
+        h: &mut HitRecord,
+    ) -> bool {
+        let mut hitted = world.hit(r, t_max, 0.001, h);
+        if let Some(fog) = &self.fog {
+            // the fog can scatter the ray before it reach the object
+            let t_hit = if hitted { h.t } else { t_max };
+            hitted |= fog.hit(r, &world.bounding_box(), t_hit, h);
+        }
+        hitted
+    }
+
+    fn background(&self, r: &Ray) -> Color {
+        if let Some(env) = &self.environment {
+            return env.radiance(&r.direction.unit_vec());
+        }
+        let unit = r.direction.unit_vec();
+        let a = 0.5 * (unit[1] + 1.0);
+        Color::new(1.0, 1.0, 1.0).mul(1.0 - a) + Color::new(0.5, 0.7, 1.0).mul(a)
+    }
+
+    // background seen by a ray that escaped, when the bounce that made it
+    // could also sample the environment, weight by MIS
+    // bsdf_pdf is the pdf of that bounce, 0.0 for camera rays and specular
+    pub fn escaped(&self, r: &Ray, bsdf_pdf: f64) -> Color {
+        let l = self.background(r);
+        match &self.environment {
+            Some(env) if bsdf_pdf > 0.0 => {
+                let light_pdf = env.pdf(&r.direction.unit_vec());
+                l.mul(power_heuristic(bsdf_pdf, light_pdf))
+            }
+            _ => l,
+        }
+    }
+
+    // next event estimation, light from the environment arriving direct in
+    // the hit, the materials without eval(specular) get nothing here
+    // return the weight of the material and the radiance, apart for the spectral
+    pub fn sample_environment(
+        &self,
+        r: &Ray,
+        h: &HitRecord,
+        world: &HittableList,
+    ) -> Option<(Color, Color)> {
+        let env = self.environment.as_ref()?;
+        let (wi, l, light_pdf) = env.sample()?;
+        let f = h.mat.eval(r, h, &wi);
+        if f.rgb_array().iter().all(|c| *c == 0.0) {
+            return None;
+        }
+        // something in the way, the fog included
+        let shadow = r.spawn(h.point, wi);
+        if self.hit_world(&shadow, world, &mut HitRecord::default()) {
+            return None;
+        }
+        let w = power_heuristic(light_pdf, h.mat.pdf(r, h, &wi));
+        Some((f.mul(w / light_pdf), l))
+    }
+
+    // next event estimation of one of the lights of the list, the delta lights
+    // have no pdf to weight, they are only reached here
+    fn sample_lights(
+        &self,
+        r: &Ray,
+        h: &HitRecord,
+        world: &HittableList,
+    ) -> Option<(Color, Color)> {
+        let (_, light, prob) = self.lights.choose(&h.point)?;
+        let ls = light.sample_li(&h.point)?;
+        let f = h.mat.eval(r, h, &ls.wi);
+        if f.rgb_array().iter().all(|c| *c == 0.0) {
+            return None;
+        }
+        // the wi is unit, so the t is the distance
+        let shadow = r.spawn(h.point, ls.wi);
+        // a bit short, the lights with geometry would hide themselves
+        let dist = ls.dist * (1.0 - 1e-6);
+        if self.hit_world_until(&shadow, world, dist, &mut HitRecord::default()) {
+            return None;
+        }
+        let w = if light.is_delta() {
+            1.0
+        } else {
+            power_heuristic(ls.pdf * prob, h.mat.pdf(r, h, &ls.wi))
+        };
+        Some((f.mul(w / (ls.pdf * prob)), ls.li))
+    }
+
+    // MIS weight of the emission of a hit by a bounce, the lights of the list
+    // with geometry were also reached by the next event estimation
+    fn emission_weight(&self, r: &Ray, h: &HitRecord, bsdf_pdf: f64) -> f64 {
+        if bsdf_pdf <= 0.0 {
+            return 1.0;
+        }
+        let Some((id, light)) = h.light.and_then(|id| Some((id, self.lights.get(id)?))) else {
+            return 1.0;
+        };
+        let wi = r.direction.unit_vec();
+        let light_pdf = self.lights.pmf(id, &r.origin) * light.pdf_li(&r.origin, &wi);
+        power_heuristic(bsdf_pdf, light_pdf)
+    }
+
+    // emission of a hit in a light that the photon map already have
+    fn in_photon_map(&self, h: &HitRecord, path: CausticPath) -> bool {
+        self.caustics.is_some() && path == CausticPath::Specular && h.light.is_some()
+    }
+
+    // light of the photons near a hit that is not delta, whatever lobe the
+    // path choose next, the photons are stored in all of them
+    fn caustics(&self, r: &Ray, h: &HitRecord) -> Color {
+        match &self.caustics {
+            Some(map) if !h.mat.is_delta(h) && !h.mat.is_medium() => map.estimate(r, h),
+            _ => Color::default(),
+        }
+    }
+
+    // all the light arriving direct in the hit, by next event estimation
+    fn sample_direct(
+        &self,
+        r: &Ray,
+        h: &HitRecord,
+        world: &HittableList,
+    ) -> impl Iterator<Item = (Color, Color)> {
+        self.sample_environment(r, h, world)
+            .into_iter()
+            .chain(self.sample_lights(r, h, world))
+    }
+
     // based in the objects get the color of pixel pointed from the ray
-    fn ray_color(r: &Ray, world: &HittableList, deep: u8) -> Color {
+    // path is where the path is for the caustics of the photon map
+    fn ray_color(
+        &self,
+        r: &Ray,
+        world: &HittableList,
+        deep: u8,
+        bsdf_pdf: f64,
+        path: CausticPath,
+    ) -> Color {
         if deep == 0 {
             return Color::default();
         }
         let mut h = HitRecord::default();
-        if world.hit(r, INF, 0.001, &mut h) {
+        if self.hit_world(r, world, &mut h) {
             // refelction based on material
             let mut reflected_r = Ray::default();
             let mut attenuation = Color::default();
             // hitted, the function of material is for check is the refelction will happen
             // and what color is attenuation be
-            if h.mat.reflect(r, &mut reflected_r, &h, &mut attenuation) {
-                return attenuation * Self::ray_color(&reflected_r, world, deep - 1);
+            let direct = self
+                .sample_direct(r, &h, world)
+                .fold(Color::default(), |acc, (f, l)| acc + f * l);
+            let mut emitted = direct;
+            if !self.in_photon_map(&h, path) {
+                emitted += h.mat.emitted(&h).mul(self.emission_weight(r, &h, bsdf_pdf));
             }
-            return Color::default();
+            if let Some(pdf) = h.mat.scatter(r, &mut reflected_r, &h, &mut attenuation) {
+                let next = path.after(&h, pdf);
+                return emitted
+                    + self.caustics(r, &h)
+                    + attenuation * self.ray_color(&reflected_r, world, deep - 1, pdf, next);
+            }
+            return emitted;
         }
-        // background
-        let unit = r.direction.unit_vec();
-        let a = 0.5 * (unit[1] + 1.0);
-        Color::new(1.0, 1.0, 1.0).mul(1.0 - a) + Color::new(0.5, 0.7, 1.0).mul(a)
+        self.escaped(r, bsdf_pdf)
+    }
+
+    // same of ray_color, but carry the spectrum in the sampled wavelengths
+    // the ray wavelength is the hero, used by the dispersive materials
+    fn ray_color_spectral(
+        &self,
+        r: &Ray,
+        world: &HittableList,
+        deep: u8,
+        bsdf_pdf: f64,
+        path: CausticPath,
+        lambdas: &mut SampledWavelengths,
+    ) -> SampledSpectrum {
+        if deep == 0 {
+            return SampledSpectrum::default();
+        }
+        let mut h = HitRecord::default();
+        if self.hit_world(r, world, &mut h) {
+            // the other wavelengths would refract to other directions
+            if h.mat.is_dispersive() {
+                lambdas.terminate_secondary();
+            }
+            let mut reflected_r = Ray::default();
+            let mut attenuation = Color::default();
+            let mut emitted = h.mat.emitted_spectral(&h, lambdas);
+            let w = if self.in_photon_map(&h, path) {
+                0.0
+            } else {
+                self.emission_weight(r, &h, bsdf_pdf)
+            };
+            emitted.values.iter_mut().for_each(|v| *v *= w);
+            for (f, l) in self.sample_direct(r, &h, world) {
+                emitted = emitted
+                    + SampledSpectrum::from_albedo(f.rgb_array(), lambdas)
+                        * SampledSpectrum::from_emission(l.rgb_array(), lambdas);
+            }
+            if let Some(pdf) = h.mat.scatter(r, &mut reflected_r, &h, &mut attenuation) {
+                let albedo = SampledSpectrum::from_albedo(attenuation.rgb_array(), lambdas);
+                let caustics = self.caustics(r, &h);
+                let next = path.after(&h, pdf);
+                return emitted
+                    + SampledSpectrum::from_emission(caustics.rgb_array(), lambdas)
+                    + albedo
+                        * self.ray_color_spectral(
+                            &reflected_r,
+                            world,
+                            deep - 1,
+                            pdf,
+                            next,
+                            lambdas,
+                        );
+            }
+            return emitted;
+        }
+        SampledSpectrum::from_emission(self.escaped(r, bsdf_pdf).rgb_array(), lambdas)
+    }
+
+    // color of a camera ray, in the mode of the camera
+    // the bidirectional can also add light in other pixels, in the splats
+    fn sample_color(&self, r: &Ray, world: &HittableList, splats: &Splats) -> Color {
+        if self.integrator == Integrator::Bidirectional {
+            return Bdpt::new(self, world, splats, r.time).li(r);
+        }
+        match self.mode {
+            RenderMode::Rgb => self.ray_color(r, world, self.max_deep_ray, 0.0, CausticPath::No),
+            RenderMode::Spectral => {
+                let mut lambdas = SampledWavelengths::sample_uniform();
+                let r = Ray {
+                    wavelength: Some(lambdas.lambda[0]),
+                    ..*r
+                };
+                let l = self.ray_color_spectral(
+                    &r,
+                    world,
+                    self.max_deep_ray,
+                    0.0,
+                    CausticPath::No,
+                    &mut lambdas,
+                );
+                let [red, green, blue] = l.to_rgb(&lambdas);
+                Color::new(red, green, blue)
+            }
+        }
+    }
+
+    pub fn origin(&self) -> Vec3 {
+        self.center
+    }
+
+    // area of the image in a plane at distance 1.0 of the camera
+    fn film_area(&self) -> f64 {
+        let dist = (self.pixel00_loc - self.center).dot(&self.w.mul(-1.0));
+        let wid = self.delta_x.vec_length() * self.image_wid as f64;
+        let hei = self.delta_y.vec_length() * self.image_hei as f64;
+        wid * hei / (dist * dist)
+    }
+
+    // importance of the camera in the direction dir, and the pdf in solid
+    // angle of get_ray choose it, so a ray of get_ray has importance * cos / pdf
+    // of 1.0, zero out of the image
+    pub fn importance(&self, dir: &Vec3) -> (f64, f64) {
+        if self.raster(dir).is_none() {
+            return (0.0, 0.0);
+        }
+        let cos = dir.unit_vec().dot(&self.w.mul(-1.0));
+        let area = self.film_area();
+        (1.0 / (area * cos.powi(4)), 1.0 / (area * cos.powi(3)))
+    }
+
+    // pixel seen in the direction dir from the camera, None out of the image
+    pub fn raster(&self, dir: &Vec3) -> Option<(u32, u32)> {
+        let forward = self.w.mul(-1.0);
+        let d = dir.unit_vec();
+        let cos = d.dot(&forward);
+        if cos <= 0.0 {
+            return None;
+        }
+        // point of the direction in the plane of the pixels
+        let dist = (self.pixel00_loc - self.center).dot(&forward);
+        let p = self.center + d.mul(dist / cos);
+        let off = p - self.pixel00_loc;
+        // the pixel i cover [i - 0.5, i + 0.5) from the pixel00
+        let x = off.dot(&self.delta_x) / self.delta_x.vec_length().powi(2) + 0.5;
+        let y = off.dot(&self.delta_y) / self.delta_y.vec_length().powi(2) + 0.5;
+        if x < 0.0 || y < 0.0 || x >= self.image_wid as f64 || y >= self.image_hei as f64 {
+            return None;
+        }
+        Some((x as u32, y as u32))
     }
 
     // will get a rondom ray from camera to arround the i, j pixel
-    fn get_ray(&self, i: u32, j: u32, random_near: bool) -> Ray {
+    pub fn get_ray(&self, i: u32, j: u32, random_near: bool) -> Ray {
         let offset = if random_near {
             sample_square() // get a random vec offset
         } else {
@@ -76,35 +459,180 @@ impl Camera {
 
         // same used, get a ray from camera to the pixel
         let ray_dir = pixel_sample - self.center;
-        Ray::new(self.center, ray_dir)
+        let ray_time = utils::random_Interval_f64(self.shutter_open, self.shutter_close);
+        Ray::new_timed(self.center, ray_dir, ray_time)
     }
 
     pub fn render(&mut self, world: &HittableList) -> RgbImage {
-        self.inititalize();
-        let mut buffer: RgbImage = ImageBuffer::new(self.image_wid, self.image_hei);
-        let antialiasing = true;
-        let sample = if antialiasing {
-            (self.samples_per_pixel, true)
-        } else {
-            (1, false)
+        let stop = Progressive::new(self.samples_per_pixel as u32);
+        self.render_progressive(world, stop, |_, _| true)
+    }
+
+    // render in passes of one sample for each pixel, until the stop
+    // after each pass on_pass get the film so far, Film::image is the
+    // preview, false stop the render
+    pub fn render_progressive<F>(
+        &mut self,
+        world: &HittableList,
+        stop: Progressive,
+        on_pass: F,
+    ) -> RgbImage
+    where
+        F: FnMut(&Progress, &Film) -> bool,
+    {
+        self.inititalize(world);
+        let mut film = Film::new(self.image_wid, self.image_hei);
+        self.render_passes(world, &mut film, stop, on_pass);
+        film.image()
+    }
+
+    // same of render_progressive, but the film is saved in the checkpoint
+    // at each interval and in the end, if the checkpoint exists the render
+    // continue from it, to the samples of the stop
+    // with a crop only its pixels get the new samples, merged in the film
+    // error if the checkpoint was made from other scene or camera
+    pub fn render_checkpointed<F>(
+        &mut self,
+        world: &HittableList,
+        stop: Progressive,
+        checkpoint: &Checkpoint,
+        mut on_pass: F,
+    ) -> io::Result<RgbImage>
+    where
+        F: FnMut(&Progress, &Film) -> bool,
+    {
+        self.inititalize(world);
+        let fingerprint = checkpoint::fingerprint(self, world);
+        let mut film = match checkpoint.load(fingerprint, self.image_wid, self.image_hei)? {
+            Some(film) => film,
+            None => Film::new(self.image_wid, self.image_hei),
         };
 
-        buffer.par_enumerate_pixels_mut().for_each(|(x, y, pixel)| {
-            // parallel the antialiasing
-            let c = (0..sample.0)
-                .into_par_iter()
-                .map(|_| {
-                    let r = self.get_ray(x, y, sample.1);
-                    Self::ray_color(&r, world, self.max_deep_ray)
-                })
-                .reduce(Color::default, |a, b| a + b);
-            *pixel = Rgb::from(c.mul(self.pixel_samples_scale));
+        let mut saved = Instant::now();
+        let mut result = Ok(());
+        self.render_passes(world, &mut film, stop, |p, film| {
+            if saved.elapsed() >= checkpoint.interval {
+                saved = Instant::now();
+                if let Err(e) = checkpoint.save(film, fingerprint) {
+                    result = Err(e);
+                    return false;
+                }
+            }
+            on_pass(p, film)
+        });
+        result?;
+        checkpoint.save(&film, fingerprint)?;
+        Ok(film.image())
+    }
+
+    // the passes of the progressive render, from the samples already in the
+    // film, the time of the stop count from now
+    fn render_passes<F>(
+        &mut self,
+        world: &HittableList,
+        film: &mut Film,
+        stop: Progressive,
+        mut on_pass: F,
+    ) where
+        F: FnMut(&Progress, &Film) -> bool,
+    {
+        let start = Instant::now();
+
+        // the samples are split in the passes of the photon map, each with
+        // new photons and a smaller radius
+        // the samples of a pass are kept in the film, a resumed render with
+        // a bigger stop go on to the next passes, not back to the first ones
+        // the bidirectional already find the caustics by itself
+        let photons = self
+            .photons
+            .filter(|_| self.integrator == Integrator::PathTracer && !self.lights.is_empty());
+        let photon_samples = photons.map_or(1, |p| {
+            film.photon_samples(stop.max_samples().div_ceil(p.passes.max(1)))
+        });
+        let mut photon_pass = None;
+
+        let region = self.region();
+        while film.min_samples(&region) < stop.max_samples() {
+            let current = film.min_samples(&region) / photon_samples;
+            if let Some(p) = photons
+                && photon_pass != Some(current)
+            {
+                photon_pass = Some(current);
+                // free the old photons before trace the new
+                self.caustics = None;
+                let seed = utils::hash_seed(&[film.seed(), PHOTON_STREAM, current as u64]);
+                let caustics = PhotonMap::trace(self, world, p.photons, p.radius_at(current), seed);
+                self.caustics = Some(caustics);
+            }
+
+            self.render_pass(world, film);
+
+            let progress = Progress {
+                samples: film.min_samples(&region),
+                elapsed: start.elapsed(),
+                noise: film.noise(&region),
+            };
+            if !on_pass(&progress, film) || stop.done(&progress) {
+                break;
+            }
+        }
+        self.caustics = None;
+    }
+
+    // one more sample in all the pixels of the film
+    fn render_pass(&self, world: &HittableList, film: &mut Film) {
+        let antialiasing = true;
+        let tiles = tile::tiles(&self.region(), self.tile_size, self.tile_order);
+        // each thread take the next tile, so they are done in the order
+        let next = AtomicUsize::new(0);
+        let done = Mutex::new(Vec::with_capacity(tiles.len()));
+        // the bidirectional add light in any pixel, in the splats of the film
+        let splats = film.splats();
+        rayon::scope(|s| {
+            for _ in 0..rayon::current_num_threads() {
+                s.spawn(|_| {
+                    while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let colors: Vec<Color> = tile
+                            .pixels()
+                            .map(|(x, y)| {
+                                // the random numbers of a sample come from the
+                                // seed of the film, the sample and the pixel,
+                                // so a resumed render continue the same samples
+                                let pixel = (y * self.image_wid + x) as u64;
+                                let sample = film.samples(x, y) as u64;
+                                utils::seed_rng(utils::hash_seed(&[film.seed(), sample, pixel]));
+                                let r = self.get_ray(x, y, antialiasing);
+                                self.sample_color(&r, world, splats)
+                            })
+                            .collect();
+                        done.lock().unwrap().push((*tile, colors));
+                    }
+                });
+            }
         });
+        for (tile, colors) in done.into_inner().unwrap() {
+            film.add_tile(&tile, &colors);
+        }
+    }
+
+    // the pixels rendered, the crop or all the image
+    pub fn region(&self) -> Region {
+        let all = Region::new(0, 0, self.image_wid, self.image_hei);
+        self.crop
+            .map_or(all, |c| c.clamp(self.image_wid, self.image_hei))
+    }
 
-        buffer
+    // pixels of the whole image
+    pub fn image_area(&self) -> u32 {
+        self.image_wid * self.image_hei
     }
 
-    fn inititalize(&mut self) {
+    fn inititalize(&mut self, world: &HittableList) {
+        assert!(
+            !(self.integrator == Integrator::Bidirectional && self.mode == RenderMode::Spectral),
+            "the bidirectional integrator only render in rgb, use the path tracer for spectral"
+        );
+        self.lights.build(&world.bounding_box());
         self.image_hei = (self.image_wid as f64 / self.aspect_ratio) as u32;
 
         self.center = self.lookfrom;
@@ -134,18 +662,28 @@ impl Camera {
     }
 
     pub fn new(aspect: f64, img_wid: u32) -> Self {
-        let mut f = Camera::default();
-        f.aspect_ratio = aspect;
-        f.image_wid = img_wid;
-        f.samples_per_pixel = 7;
-        f.pixel_samples_scale = 1.0 / f.samples_per_pixel as f64;
-        f.max_deep_ray = 15;
-        f.vfov = 90.0;
+        Camera {
+            aspect_ratio: aspect,
+            image_wid: img_wid,
+            samples_per_pixel: 7,
+            max_deep_ray: 15,
+            tile_size: 32,
+            vfov: 90.0,
+
+            lookfrom: Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 0.0),
+            lookat: Vec3::new(VecTypes::Coordinates, 0.0, 0.0, -1.0),
+            vup: Vec3::new(VecTypes::Coordinates, 0.0, 1.0, 0.0),
 
-        f.lookfrom = Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 0.0);
-        f.lookat = Vec3::new(VecTypes::Coordinates, 0.0, 0.0, -1.0);
-        f.vup = Vec3::new(VecTypes::Coordinates, 0.0, 1.0, 0.0);
+            ..Default::default()
+        }
+    }
+}
 
-        f
+// MIS weight of the strategy with pdf f, against the other with pdf g
+fn power_heuristic(f: f64, g: f64) -> f64 {
+    let (f2, g2) = (f * f, g * g);
+    if f2 + g2 == 0.0 {
+        return 0.0;
     }
+    f2 / (f2 + g2)
 }
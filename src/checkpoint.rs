@@ -0,0 +1,276 @@
+use std::{
+    f64::consts::PI,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    camera::Camera,
+    film::Film,
+    ray::{HitRecord, Hittable, HittableList},
+    utils::{self, INF},
+    vec::{Vec3, VecTypes},
+};
+
+const MAGIC: &[u8; 4] = b"RTCK";
+const VERSION: u32 = 2;
+
+// directions where the environment and the materials are probed, and rays
+// of the camera in each side of the grid
+const PROBES: usize = 16;
+const PROBES_SIDE: u32 = 8;
+
+// FNV-1a, stable between runs and versions of rust, unlike the std hasher
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.bytes(&v.to_le_bytes());
+    }
+
+    fn f64(&mut self, v: f64) {
+        self.u64(v.to_bits());
+    }
+
+    fn vec(&mut self, v: &Vec3) {
+        for a in 0..3 {
+            self.f64(v[a]);
+        }
+    }
+}
+
+// directions spread over the sphere, by the golden angle
+fn probe_directions() -> impl Iterator<Item = Vec3> {
+    (0..PROBES).map(|i| {
+        let z = 1.0 - 2.0 * (i as f64 + 0.5) / PROBES as f64;
+        let r = (1.0 - z * z).sqrt();
+        let phi = PI * (3.0 - 5.0_f64.sqrt()) * i as f64;
+        Vec3::new(VecTypes::Coordinates, r * phi.cos(), r * phi.sin(), z)
+    })
+}
+
+// what a film depends on, the camera(without the samples) and a summary of
+// the world, its objects and bounds, and of the lights, their power and bounds
+// the environment is seen by its light in some directions, and the materials
+// and textures by a grid of rays of the camera, the distance, emission and
+// scattering where each one hit first
+// the perfect specular materials don't scatter to a given direction, a
+// change in them is not found
+pub fn fingerprint(cam: &Camera, world: &HittableList) -> u64 {
+    let mut h = Fnv::new();
+    h.u64(cam.image_wid as u64);
+    h.f64(cam.aspect_ratio);
+    h.f64(cam.vfov);
+    h.vec(&cam.lookfrom);
+    h.vec(&cam.lookat);
+    h.vec(&cam.vup);
+    h.f64(cam.shutter_open);
+    h.f64(cam.shutter_close);
+    h.u64(cam.max_deep_ray as u64);
+    h.u64(cam.mode as u64);
+    h.u64(cam.integrator as u64);
+    if let Some(fog) = &cam.fog {
+        h.f64(fog.density);
+        h.vec(&fog.albedo.rgb);
+    }
+    if let Some(p) = &cam.photons {
+        h.u64(p.photons as u64);
+        h.u64(p.passes as u64);
+        h.f64(p.radius);
+        h.f64(p.alpha);
+    }
+    h.u64(cam.environment.is_some() as u64);
+    if let Some(env) = &cam.environment {
+        for dir in probe_directions() {
+            h.vec(&env.radiance(&dir).rgb);
+            h.f64(env.pdf(&dir));
+        }
+    }
+    h.u64(cam.lights.len() as u64);
+    for light in (0..cam.lights.len()).filter_map(|id| cam.lights.get(id)) {
+        h.f64(light.power(1.0));
+        if let Some(b) = light.bounds() {
+            h.vec(&b.bbox.min());
+            h.vec(&b.bbox.max());
+        }
+    }
+    h.u64(world.len() as u64);
+    let bbox = world.bounding_box();
+    h.vec(&bbox.min());
+    h.vec(&bbox.max());
+
+    // the random numbers of the rays, and of the materials that scatter by
+    // a random walk, are the same in each run
+    let (wid, hei) = (cam.image_wid, cam.image_area() / cam.image_wid.max(1));
+    for j in 0..PROBES_SIDE {
+        for i in 0..PROBES_SIDE {
+            let (x, y) = (i * wid / PROBES_SIDE, j * hei / PROBES_SIDE);
+            utils::seed_rng(utils::hash_seed(&[x as u64, y as u64]));
+            let r = cam.get_ray(x, y, false);
+            let mut rec = HitRecord::default();
+            if !world.hit(&r, INF, 0.001, &mut rec) {
+                continue;
+            }
+            h.f64(rec.t);
+            h.vec(&rec.normal);
+            h.vec(&rec.mat.emitted(&rec).rgb);
+            for dir in probe_directions() {
+                h.vec(&rec.mat.eval(&r, &rec, &dir).rgb);
+            }
+        }
+    }
+    h.0
+}
+
+// where and how often a long render save its film, to continue after the
+// process die, or later to more samples
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+impl Checkpoint {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Checkpoint {
+            path: path.as_ref().to_path_buf(),
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    // written in a temporary file and renamed, a kill in the middle don't
+    // break the last checkpoint
+    pub fn save(&self, film: &Film, fingerprint: u64) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&fingerprint.to_le_bytes())?;
+        film.write(&mut w)?;
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(tmp, &self.path)
+    }
+
+    // None if there is no checkpoint yet, error if it is not a checkpoint
+    // or it was made from other scene, camera or image size
+    pub fn load(&self, fingerprint: u64, wid: u32, hei: u32) -> io::Result<Option<Film>> {
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut r = BufReader::new(file);
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint"));
+        }
+        let mut b = [0; 4];
+        r.read_exact(&mut b)?;
+        if u32::from_le_bytes(b) != VERSION {
+            return Err(invalid("checkpoint of other version"));
+        }
+        let mut b = [0; 8];
+        r.read_exact(&mut b)?;
+        if u64::from_le_bytes(b) != fingerprint {
+            return Err(invalid("checkpoint of other scene or camera"));
+        }
+        Film::read(&mut r, wid, hei).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use super::Checkpoint;
+    use crate::{
+        camera::Camera,
+        color::Color,
+        film::Progressive,
+        material::Lambertian,
+        ray::{HittableList, Sphere},
+        sky::Sky,
+        vec::{Vec3, VecTypes},
+    };
+
+    fn p(x: f64, y: f64, z: f64) -> Vec3 {
+        Vec3::new(VecTypes::Coordinates, x, y, z)
+    }
+
+    fn scene(albedo: Color) -> HittableList {
+        let mut world = HittableList::new();
+        world.add(Box::new(Sphere::new(
+            p(0.0, 0.0, 0.0),
+            1.0,
+            Box::new(Lambertian::new(albedo)),
+        )));
+        world
+    }
+
+    fn camera(turbidity: f64) -> Camera {
+        let mut c = Camera::new(1.0, 8);
+        c.lookfrom = p(0.0, 0.0, 4.0);
+        c.lookat = p(0.0, 0.0, 0.0);
+        c.vfov = 40.0;
+        c.environment = Some(Arc::new(Sky::new(30.0, 0.0, turbidity)));
+        c
+    }
+
+    // the samples of the first pass of the render and the samples in the end
+    fn render(c: &mut Camera, world: &HittableList, ck: &Checkpoint, samples: u32) -> (u32, u32) {
+        let mut first = None;
+        let mut last = 0;
+        c.render_checkpointed(world, Progressive::new(samples), ck, |progress, _| {
+            first.get_or_insert(progress.samples);
+            last = progress.samples;
+            true
+        })
+        .unwrap();
+        (first.unwrap(), last)
+    }
+
+    #[test]
+    fn resume_only_the_same_scene() {
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.ckpt", std::process::id()));
+        let ck = Checkpoint::new(&path);
+        let gray = Color::new(0.5, 0.5, 0.5);
+        let world = scene(gray);
+
+        assert_eq!(render(&mut camera(3.0), &world, &ck, 2), (1, 2));
+        // resumed to more samples, it go on from the saved ones
+        assert_eq!(render(&mut camera(3.0), &world, &ck, 4), (3, 4));
+
+        let mut moved = camera(3.0);
+        moved.lookfrom = p(0.0, 1.0, 4.0);
+        let other_sky = camera(6.0);
+        let red = scene(Color::new(0.8, 0.1, 0.1));
+        for (mut c, world) in [(moved, &world), (other_sky, &world), (camera(3.0), &red)] {
+            let r = c.render_checkpointed(world, Progressive::new(8), &ck, |_, _| true);
+            assert!(r.is_err());
+        }
+        // the failed loads don't touch the checkpoint
+        assert_eq!(render(&mut camera(3.0), &world, &ck, 5), (5, 5));
+        fs::remove_file(path).unwrap();
+    }
+}
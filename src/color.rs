@@ -7,6 +7,7 @@ use std::{
 use image::Rgb;
 
 use crate::{
+    spectrum,
     utils::{Interval, randon_f64},
     vec::{self, Vec3},
 };
@@ -30,6 +31,23 @@ impl Color {
     pub fn random() -> Self {
         Color::new(randon_f64(), randon_f64(), randon_f64())
     }
+
+    pub fn rgb_array(&self) -> [f64; 3] {
+        [self.rgb[0], self.rgb[1], self.rgb[2]]
+    }
+
+    // CIE XYZ to linear sRGB
+    pub fn from_xyz(x: f64, y: f64, z: f64) -> Self {
+        let [r, g, b] = spectrum::xyz_to_rgb([x, y, z]);
+        Color::new(r, g, b)
+    }
+
+    // rgb weight of a path with a single wavelength(nm) choosed uniformly
+    // in the visible range, the mean of all wavelengths is white
+    pub fn from_wavelength(lambda: f64) -> Self {
+        let [r, g, b] = spectrum::wavelength_to_rgb(lambda);
+        Color::new(r, g, b)
+    }
 }
 
 impl fmt::Display for Color {
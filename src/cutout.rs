@@ -0,0 +1,44 @@
+use crate::{
+    aabb::Aabb,
+    ray::{HitRecord, Hittable, Ray},
+    texture::TextureRef,
+    utils::randon_f64,
+};
+
+// alpha mask over a object, where the alpha is 0.0 the rays pass straight
+// through, so a leaf or a fence can be a simple card and not geometry
+// the alpha between 0.0 and 1.0 is the probability of the hit, semi transparent
+// the test is in the hit, so the shadow rays also see the holes
+pub struct Cutout {
+    obj: Box<dyn Hittable + Sync>,
+    alpha: TextureRef,
+}
+
+impl Cutout {
+    pub fn new(obj: Box<dyn Hittable + Sync>, alpha: TextureRef) -> Self {
+        Self { obj, alpha }
+    }
+
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+
+impl Hittable for Cutout {
+    fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
+        let mut t_min = r_min;
+        // keep looking for the next hit behind the holes
+        while self.obj.hit(r, r_max, t_min, rec) {
+            let alpha = self.alpha.scalar(rec.u, rec.v, &rec.point);
+            if alpha >= 1.0 || (alpha > 0.0 && randon_f64() < alpha) {
+                return true;
+            }
+            t_min = rec.t;
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.obj.bounding_box()
+    }
+}
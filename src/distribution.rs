@@ -0,0 +1,109 @@
+// piecewise constant distribution in [0, 1), the func don't need to be
+// normalized, the values are only the relative importance of each piece
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len().max(1);
+        let func = if func.is_empty() { vec![0.0] } else { func };
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f64;
+        }
+        let integral = cdf[n];
+        if integral == 0.0 {
+            // nothing to prefer, uniform
+            for (i, c) in cdf.iter_mut().enumerate() {
+                *c = i as f64 / n as f64;
+            }
+        } else {
+            for c in cdf.iter_mut() {
+                *c /= integral;
+            }
+        }
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // x in [0, 1) with density func(x) / integral, and the index of its piece
+    pub fn sample_continuous(&self, u: f64) -> (f64, f64, usize) {
+        let i = self.find(u);
+        let mut du = u - self.cdf[i];
+        let width = self.cdf[i + 1] - self.cdf[i];
+        if width > 0.0 {
+            du /= width;
+        }
+        let x = (i as f64 + du) / self.count() as f64;
+        (x, self.pdf(i) * self.count() as f64, i)
+    }
+
+    // a piece, with the probability of choose it
+    pub fn sample_discrete(&self, u: f64) -> (usize, f64) {
+        let i = self.find(u);
+        (i, self.pdf(i))
+    }
+
+    // probability of the piece i in the sample_discrete
+    pub fn pdf(&self, i: usize) -> f64 {
+        self.cdf[i + 1] - self.cdf[i]
+    }
+
+    // last piece with cdf <= u
+    fn find(&self, u: f64) -> usize {
+        let i = self.cdf.partition_point(|c| *c <= u);
+        i.saturating_sub(1).min(self.count() - 1)
+    }
+}
+
+// piecewise constant distribution in [0, 1)^2, a marginal for the rows
+// and a conditional for each row, func is row by row with nu values each
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], nu: usize, nv: usize) -> Self {
+        let conditional: Vec<Distribution1D> = (0..nv)
+            .map(|v| Distribution1D::new(func[v * nu..(v + 1) * nu].to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|d| d.integral()).collect());
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    // (u, v) and the density of the point
+    pub fn sample_continuous(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, iv) = self.marginal.sample_continuous(u1);
+        let (u, pdf_u, _) = self.conditional[iv].sample_continuous(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let nv = self.conditional.len();
+        let nu = self.conditional[0].count();
+        let iv = ((v * nv as f64) as usize).min(nv - 1);
+        let iu = ((u * nu as f64) as usize).min(nu - 1);
+        // both pieces, in density of [0, 1)
+        self.conditional[iv].pdf(iu) * nu as f64 * self.marginal.pdf(iv) * nv as f64
+    }
+}
@@ -0,0 +1,132 @@
+use std::{f64::consts::PI, path::Path};
+
+use image::{ImageResult, Rgb32FImage};
+
+use crate::{
+    color::Color,
+    distribution::Distribution2D,
+    utils::{degrees_to_radians, randon_f64},
+    vec::{Vec3, VecTypes},
+};
+
+// light that come from the infinite, in all the directions that escape
+// the world, used as the background of the camera and sampled as a light
+pub trait Environment: Send + Sync {
+    // radiance arriving from the direction dir(unit)
+    fn radiance(&self, dir: &Vec3) -> Color;
+
+    // a direction to the light, with its radiance and the pdf in solid angle
+    // None if there is no light to sample
+    fn sample(&self) -> Option<(Vec3, Color, f64)>;
+
+    // pdf in solid angle of sample choose dir
+    fn pdf(&self, dir: &Vec3) -> f64;
+}
+
+// equirectangular image, like the HDRIs of the sites of free assets
+// u go around the y axis and v from the top(+y) to the bottom(-y)
+// rotation(degrees) turn the image around the y axis
+pub struct EnvironmentMap {
+    image: Rgb32FImage,
+    rotation: f64,
+    intensity: f64,
+    // luminance of the pixels, with the sin of the row
+    distrib: Distribution2D,
+}
+
+impl EnvironmentMap {
+    // .hdr or .exr, the values are linear radiance
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        Ok(Self::from_image(image::open(path)?.to_rgb32f()))
+    }
+
+    pub fn from_image(image: Rgb32FImage) -> Self {
+        let (w, h) = (
+            image.width().max(1) as usize,
+            image.height().max(1) as usize,
+        );
+        let mut func = vec![0.0; w * h];
+        for y in 0..image.height() as usize {
+            // the rows near the poles cover less solid angle
+            let sin_theta = (PI * (y as f64 + 0.5) / h as f64).sin();
+            for x in 0..image.width() as usize {
+                let p = image.get_pixel(x as u32, y as u32);
+                let lum = 0.2126 * p[0] as f64 + 0.7152 * p[1] as f64 + 0.0722 * p[2] as f64;
+                func[y * w + x] = lum.max(0.0) * sin_theta;
+            }
+        }
+        EnvironmentMap {
+            distrib: Distribution2D::new(&func, w, h),
+            image,
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees_to_radians(degrees);
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    fn dir_to_uv(&self, dir: &Vec3) -> (f64, f64) {
+        let d = dir.unit_vec();
+        let theta = d[1].clamp(-1.0, 1.0).acos();
+        let phi = d[2].atan2(d[0]) + PI - self.rotation;
+        let u = (phi / (2.0 * PI)).rem_euclid(1.0);
+        (u, theta / PI)
+    }
+
+    fn uv_to_dir(&self, u: f64, v: f64) -> Vec3 {
+        let theta = v * PI;
+        let phi = u * 2.0 * PI - PI + self.rotation;
+        Vec3::new(
+            VecTypes::Coordinates,
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            theta.sin() * phi.sin(),
+        )
+    }
+
+    fn lookup(&self, u: f64, v: f64) -> Color {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Color::default();
+        }
+        // nearest pixel, the same pieces of the distribution
+        let x = ((u * self.image.width() as f64) as u32).min(self.image.width() - 1);
+        let y = ((v * self.image.height() as f64) as u32).min(self.image.height() - 1);
+        let p = self.image.get_pixel(x, y);
+        Color::new(p[0] as f64, p[1] as f64, p[2] as f64).mul(self.intensity)
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, dir: &Vec3) -> Color {
+        let (u, v) = self.dir_to_uv(dir);
+        self.lookup(u, v)
+    }
+
+    fn sample(&self) -> Option<(Vec3, Color, f64)> {
+        let ((u, v), pdf_uv) = self.distrib.sample_continuous(randon_f64(), randon_f64());
+        let sin_theta = (v * PI).sin();
+        if pdf_uv == 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+        // from the density in the image to the density in solid angle
+        let pdf = pdf_uv / (2.0 * PI * PI * sin_theta);
+        Some((self.uv_to_dir(u, v), self.lookup(u, v), pdf))
+    }
+
+    fn pdf(&self, dir: &Vec3) -> f64 {
+        let (u, v) = self.dir_to_uv(dir);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distrib.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use image::{ImageBuffer, Rgb, RgbImage};
+use rayon::prelude::*;
+
+use crate::{bdpt::Splats, color::Color, tile::Region};
+
+fn luminance(c: &Color) -> f64 {
+    let [r, g, b] = c.rgb_array();
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut b = [0; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut b = [0; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_f64<R: Read>(r: &mut R) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(r)?))
+}
+
+fn read_color<R: Read>(r: &mut R) -> io::Result<Color> {
+    Ok(Color::new(read_f64(r)?, read_f64(r)?, read_f64(r)?))
+}
+
+fn write_color<W: Write>(w: &mut W, c: &Color) -> io::Result<()> {
+    for v in c.rgb_array() {
+        w.write_all(&v.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+// accumulation of the samples of each pixel, the image is the mean
+// the square of the luminances is kept too, to know the noise
+pub struct Film {
+    wid: u32,
+    hei: u32,
+    // the random numbers of each sample come from it
+    seed: u64,
+    // samples done with each photon map, fixed by the first render of the
+    // film, 0 before it
+    photon_samples: u32,
+    sum: Vec<Color>,
+    sum_sq: Vec<f64>,
+    samples: Vec<u32>,
+    // light of the bidirectional in any pixel, scaled like the samples
+    splats: Splats,
+}
+
+impl Film {
+    pub fn new(wid: u32, hei: u32) -> Self {
+        let n = wid as usize * hei as usize;
+        Film {
+            wid,
+            hei,
+            seed: rand::random(),
+            photon_samples: 0,
+            sum: vec![Color::default(); n],
+            sum_sq: vec![0.0; n],
+            samples: vec![0; n],
+            splats: Splats::new(wid, hei),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.wid
+    }
+
+    pub fn height(&self) -> u32 {
+        self.hei
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // the samples of each photon pass, the first call fix it, so a resumed
+    // render continue the passes where they stopped, whatever its stop
+    pub fn photon_samples(&mut self, first: u32) -> u32 {
+        if self.photon_samples == 0 {
+            self.photon_samples = first.max(1);
+        }
+        self.photon_samples
+    }
+
+    pub fn splats(&self) -> &Splats {
+        &self.splats
+    }
+
+    // one more sample in each pixel of the tile, the colors in rows
+    pub fn add_tile(&mut self, tile: &Region, colors: &[Color]) {
+        for ((x, y), c) in tile.pixels().zip(colors) {
+            let i = (y * self.wid + x) as usize;
+            let l = luminance(c);
+            self.sum[i] += *c;
+            self.sum_sq[i] += l * l;
+            self.samples[i] += 1;
+        }
+    }
+
+    pub fn samples(&self, x: u32, y: u32) -> u32 {
+        self.samples[(y * self.wid + x) as usize]
+    }
+
+    // fewest samples of a pixel of the region, the passes done in it
+    pub fn min_samples(&self, region: &Region) -> u32 {
+        region
+            .pixels()
+            .map(|(x, y)| self.samples(x, y))
+            .min()
+            .unwrap_or(0)
+    }
+
+    // mean of the samples of the pixel
+    pub fn color(&self, x: u32, y: u32) -> Color {
+        let i = (y * self.wid + x) as usize;
+        if self.samples[i] == 0 {
+            return Color::default();
+        }
+        (self.sum[i] + self.splats.get(x, y)).mul(1.0 / self.samples[i] as f64)
+    }
+
+    pub fn image(&self) -> RgbImage {
+        let mut buffer: RgbImage = ImageBuffer::new(self.wid, self.hei);
+        buffer
+            .par_enumerate_pixels_mut()
+            .for_each(|(x, y, pixel)| *pixel = Rgb::from(self.color(x, y)));
+        buffer
+    }
+
+    // mean of the error of the pixels of the region, in the displayed
+    // value(gamma 2) from 0.0 to 1.0, the standard error of the mean
+    // luminance of each pixel
+    // the splats don't count, the pixels with less than 2 samples are 1.0
+    pub fn noise(&self, region: &Region) -> f64 {
+        if region.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = (region.y0..region.y1)
+            .into_par_iter()
+            .flat_map_iter(|y| (region.x0..region.x1).map(move |x| (y * self.wid + x) as usize))
+            .map(|i| {
+                let n = self.samples[i] as f64;
+                if n < 2.0 {
+                    return 1.0;
+                }
+                let mean = luminance(&self.sum[i]) / n;
+                let variance = (self.sum_sq[i] / n - mean * mean).max(0.0) / (n - 1.0);
+                // d sqrt(x) = dx / (2 sqrt(x)), the black pixels are an step of 8 bits
+                let shown = mean.max(0.0).sqrt().max(1.0 / 256.0);
+                (variance.sqrt() / (2.0 * shown)).min(1.0)
+            })
+            .sum();
+        total / region.area() as f64
+    }
+
+    // all the film in little endian, the size, the seed, the samples of the
+    // photon passes and for each pixel
+    // the sum, the sum of squares, the samples and the splats
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.wid.to_le_bytes())?;
+        w.write_all(&self.hei.to_le_bytes())?;
+        w.write_all(&self.seed.to_le_bytes())?;
+        w.write_all(&self.photon_samples.to_le_bytes())?;
+        for i in 0..self.samples.len() {
+            let (x, y) = (i as u32 % self.wid, i as u32 / self.wid);
+            write_color(w, &self.sum[i])?;
+            w.write_all(&self.sum_sq[i].to_le_bytes())?;
+            w.write_all(&self.samples[i].to_le_bytes())?;
+            write_color(w, &self.splats.get(x, y))?;
+        }
+        Ok(())
+    }
+
+    // the size is checked before the film is allocated, a broken file can't
+    // ask for any size
+    pub fn read<R: Read>(r: &mut R, wid: u32, hei: u32) -> io::Result<Self> {
+        let size = (read_u32(r)?, read_u32(r)?);
+        if size != (wid, hei) || wid.checked_mul(hei).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "film of other image size",
+            ));
+        }
+        let mut film = Film::new(wid, hei);
+        film.seed = read_u64(r)?;
+        film.photon_samples = read_u32(r)?;
+        for i in 0..film.samples.len() {
+            let (x, y) = (i as u32 % wid, i as u32 / wid);
+            film.sum[i] = read_color(r)?;
+            film.sum_sq[i] = read_f64(r)?;
+            film.samples[i] = read_u32(r)?;
+            film.splats.add(x, y, read_color(r)?);
+        }
+        Ok(film)
+    }
+}
+
+// with few samples the variance miss the rare bright paths, so the noise
+// only can stop the render after these
+const MIN_NOISE_SAMPLES: u32 = 16;
+
+// when a progressive render stop, the first one reached
+// each pass is one more sample in all the pixels of the region
+// the fields are private, so there is always at least one sample
+#[derive(Debug, Clone, Copy)]
+pub struct Progressive {
+    max_samples: u32,
+    time: Option<Duration>,
+    noise: Option<f64>,
+}
+
+impl Progressive {
+    pub fn new(max_samples: u32) -> Self {
+        Progressive {
+            max_samples: max_samples.max(1),
+            time: None,
+            noise: None,
+        }
+    }
+
+    pub fn max_samples(&self) -> u32 {
+        self.max_samples
+    }
+
+    pub fn with_time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    // the Film::noise to stop, like 0.005 for about one step of 8 bits
+    pub fn with_noise(mut self, noise: f64) -> Self {
+        self.noise = Some(noise);
+        self
+    }
+
+    pub fn done(&self, progress: &Progress) -> bool {
+        progress.samples >= self.max_samples
+            || self.time.is_some_and(|t| progress.elapsed >= t)
+            || self
+                .noise
+                .is_some_and(|n| progress.samples >= MIN_NOISE_SAMPLES && progress.noise <= n)
+    }
+}
+
+// state of the render after a pass
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    pub samples: u32,
+    pub elapsed: Duration,
+    pub noise: f64,
+}
@@ -0,0 +1,136 @@
+use crate::{
+    aabb::Aabb,
+    ray::{HitRecord, Hittable, Ray},
+    utils::{self, Interval},
+    vec::{Vec3, VecTypes},
+};
+
+// wrap a object with a translation and a rotation arround the y axis
+// both can change with the time, from the *0 values in time 0.0
+// to the *1 values in time 1.0, giving motion blur to any Hittable
+pub struct Instance {
+    obj: Box<dyn Hittable + Sync>,
+    offset0: Vec3,
+    offset1: Vec3,
+    // angles in degrees
+    angle0: f64,
+    angle1: f64,
+    bbox: Aabb,
+}
+
+// rotate p arround y by the angle(in radians)
+fn rotate_y(p: &Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    Vec3::new(
+        p.typ,
+        cos * p[0] + sin * p[2],
+        p[1],
+        -sin * p[0] + cos * p[2],
+    )
+}
+
+impl Instance {
+    // static instance, only translated and rotated
+    pub fn new(obj: Box<dyn Hittable + Sync>, offset: Vec3, angle: f64) -> Self {
+        Self::new_moving(obj, offset, offset, angle, angle)
+    }
+
+    pub fn new_moving(
+        obj: Box<dyn Hittable + Sync>,
+        offset0: Vec3,
+        offset1: Vec3,
+        angle0: f64,
+        angle1: f64,
+    ) -> Self {
+        let bbox = Self::motion_box(&obj.bounding_box(), offset0, offset1, angle0, angle1);
+        Instance {
+            obj,
+            offset0,
+            offset1,
+            angle0,
+            angle1,
+            bbox,
+        }
+    }
+
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+
+    // transform in the time t, (offset, angle in radians)
+    fn at(&self, t: f64) -> (Vec3, f64) {
+        let offset = self.offset0 + (self.offset1 - self.offset0).mul(t);
+        let angle = self.angle0 + (self.angle1 - self.angle0) * t;
+        (offset, utils::degrees_to_radians(angle))
+    }
+
+    // box that contains the object in all the path
+    fn motion_box(b: &Aabb, offset0: Vec3, offset1: Vec3, angle0: f64, angle1: f64) -> Aabb {
+        let rotated = if angle0 == angle1 {
+            // the exact box of the rotated corners
+            let theta = utils::degrees_to_radians(angle0);
+            let mut min = Vec3::new(VecTypes::Coordinates, utils::INF, utils::INF, utils::INF);
+            let mut max = min.mul(-1.0);
+            for c in b.corners() {
+                let r = rotate_y(&c, theta);
+                for i in 0..3 {
+                    min[i] = min[i].min(r[i]);
+                    max[i] = max[i].max(r[i]);
+                }
+            }
+            Aabb::from_points(min, max)
+        } else {
+            // while rotating the corners go arround a cylinder in y,
+            // so the box of this cylinder is enough
+            let radius = b
+                .corners()
+                .iter()
+                .map(|c| (c[0].powi(2) + c[2].powi(2)).sqrt())
+                .fold(0.0, f64::max);
+            Aabb::new(
+                Interval::new(-radius, radius),
+                b.y,
+                Interval::new(-radius, radius),
+            )
+        };
+        let moved = |o: Vec3| {
+            Aabb::new(
+                Interval::new(rotated.x.min + o[0], rotated.x.max + o[0]),
+                Interval::new(rotated.y.min + o[1], rotated.y.max + o[1]),
+                Interval::new(rotated.z.min + o[2], rotated.z.max + o[2]),
+            )
+        };
+        Aabb::union(&moved(offset0), &moved(offset1))
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
+        let (offset, theta) = self.at(r.time);
+
+        // move the ray to the object space
+        let origin = rotate_y(&(r.origin - offset), -theta);
+        let direction = rotate_y(&r.direction, -theta);
+        let obj_r = Ray {
+            origin,
+            direction,
+            ..*r
+        };
+
+        if !self.obj.hit(&obj_r, r_max, r_min, rec) {
+            return false;
+        }
+
+        // and the hit back to world space, the transform is rigid
+        // so the front_face still valid
+        rec.point = rotate_y(&rec.point, theta) + offset;
+        rec.normal = rotate_y(&rec.normal, theta);
+        rec.tangent = rotate_y(&rec.tangent, theta);
+        rec.bitangent = rotate_y(&rec.bitangent, theta);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
@@ -0,0 +1,337 @@
+use std::f64::consts::PI;
+
+use crate::{
+    color::Color,
+    material::Material,
+    microfacet::{TrowbridgeReitz, fresnel_dielectric, reflect, refract},
+    onb::Onb,
+    ray::{HitRecord, Ray},
+    utils::randon_f64,
+    vec::{Vec3, VecTypes},
+};
+
+// bounces between the base and the top of the coat before the russian
+// roulette, that end the long walks without losing energy in the mean
+const ROULETTE_WALK: u32 = 4;
+
+// same direction seen from the other side of the surface
+fn flip(w: &Vec3) -> Vec3 {
+    Vec3::new(VecTypes::Coordinates, w[0], w[1], -w[2])
+}
+
+// the walk is ended by the russian roulette after some bounces
+fn survive(walk: u32, throughput: Color) -> Option<Color> {
+    if walk < ROULETTE_WALK {
+        return Some(throughput);
+    }
+    let q = throughput
+        .rgb_array()
+        .into_iter()
+        .fold(0.0, f64::max)
+        .min(0.95);
+    if randon_f64() >= q {
+        return None;
+    }
+    Some(throughput.mul(1.0 / q))
+}
+
+// a dieletric coat over any other material, like car paint or varnished wood
+// the light reflect in the coat(rough GGX) or cross it and do a random walk
+// between the base and the top, absorbed by the thickness of the coat
+// each step only keep or lose energy, so it never reflect more than enter
+// a rough coat lose the light masked by its microfacets, in and out, like
+// the RoughDieletric
+// the eval is a stochastic estimate of the same walk(like the LayeredBxDF of
+// pbrt), so the coat is light sampled, and the pdf is an aproximation
+pub struct Layered {
+    inner: Box<dyn Material>,
+    ior: f64,
+    distrib: TrowbridgeReitz,
+    // absorption coefficient of the coat, by unit of distance
+    sigma_a: Color,
+    thickness: f64,
+}
+
+impl Layered {
+    pub fn new(inner: Box<dyn Material>, ior: f64, roughness: f64) -> Self {
+        Self {
+            inner,
+            ior,
+            distrib: TrowbridgeReitz::from_roughness(roughness),
+            sigma_a: Color::default(),
+            thickness: 0.0,
+        }
+    }
+
+    // colored coat, sigma_a by unit of distance and the thickness of the coat
+    pub fn with_absorption(mut self, sigma_a: Color, thickness: f64) -> Self {
+        self.sigma_a = sigma_a;
+        self.thickness = thickness;
+        self
+    }
+
+    // transmittance of crossing the coat in a direction with the cos
+    fn absorption(&self, cos: f64) -> Color {
+        let d = self.thickness / cos.abs().max(1e-4);
+        Color::new(
+            (-self.sigma_a.rgb[0] * d).exp(),
+            (-self.sigma_a.rgb[1] * d).exp(),
+            (-self.sigma_a.rgb[2] * d).exp(),
+        )
+    }
+
+    // the light coming from wo(z > 0) in the top of the coat reflect or cross
+    // it by a microfacet normal, eta is the index of the other side over the
+    // one of wo, with the weight of the masking
+    // the direction, the weight and if it crossed, None if it is masked
+    fn interface(&self, wo: &Vec3, eta: f64) -> Option<(Vec3, f64, bool)> {
+        let smooth = self.distrib.effectively_smooth();
+        let wm = if smooth {
+            Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 1.0)
+        } else {
+            self.distrib.sample_wm(wo)
+        };
+        let reflected = randon_f64() < fresnel_dielectric(wo.dot(&wm), eta);
+        let wi = if reflected {
+            reflect(wo, &wm)
+        } else {
+            refract(wo, &wm, eta)?
+        };
+        // a reflection must stay in the side of wo, a refraction cross it
+        if (wi[2] > 0.0) != reflected {
+            return None;
+        }
+        let g = if smooth {
+            1.0
+        } else {
+            self.distrib.g(wo, &wi) / self.distrib.g1(wo)
+        };
+        Some((wi, g, !reflected))
+    }
+
+    // the light going down in the coat reach the base and go back up, the
+    // direction, the weight and the pdf of the lobe of the base
+    fn base(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        frame: &Onb,
+        w_down: &Vec3,
+    ) -> Option<(Vec3, Color, f64)> {
+        // the base see the ray coming from inside the coat
+        let r_base = r_in.spawn(rec.point, frame.transform(w_down));
+        let mut r_out = Ray::default();
+        let mut att = Color::default();
+        let pdf = self.inner.scatter(&r_base, &mut r_out, rec, &mut att)?;
+        let w_up = frame.inverse_transform(&r_out.direction.unit_vec());
+        // transmited by the base, lost for the coat
+        if w_up[2] <= 0.0 {
+            return None;
+        }
+        Some((w_up, att * self.absorption(w_up[2]), pdf))
+    }
+
+    // the direction going up in the coat that leave by wi with a smooth top
+    fn inside(&self, wi: &Vec3) -> Option<Vec3> {
+        let n = Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 1.0);
+        refract(wi, &n, self.ior).map(|t| t.mul(-1.0))
+    }
+
+    // the direction that leave the coat, the weight and if it was only by
+    // delta lobes, None if the light is lost
+    fn sample(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        frame: &Onb,
+        wo: &Vec3,
+    ) -> Option<(Vec3, Color, bool)> {
+        let smooth = self.distrib.effectively_smooth();
+        // reflection in the top of the coat or enter it
+        let (wi, g, crossed) = self.interface(wo, self.ior)?;
+        if !crossed {
+            return Some((wi, Color::new(g, g, g), smooth));
+        }
+        let mut delta = smooth;
+        let mut w_down = wi;
+        let mut throughput = self.absorption(w_down[2]).mul(g);
+
+        for walk in 0.. {
+            let (w_up, att, pdf) = self.base(r_in, rec, frame, &w_down)?;
+            delta &= pdf == 0.0;
+            throughput = throughput * att;
+
+            // leave by the top or reflect back to the base, seen from inside
+            // the top is a surface with the normal down
+            let (wi, g, crossed) = self.interface(&flip(&w_up.mul(-1.0)), 1.0 / self.ior)?;
+            throughput = throughput.mul(g);
+            if crossed {
+                return Some((flip(&wi), throughput, delta));
+            }
+            w_down = flip(&wi);
+            throughput = survive(walk, throughput * self.absorption(w_down[2]))?;
+        }
+        None
+    }
+
+    // f * cos of the reflection in the top, more a walk like the one of
+    // sample, but in each bounce in the base it add the light that leave by
+    // wi, by the eval of the base with a smooth top, or by the eval of the
+    // top after the base choose the direction with a rough one
+    // the walk stop when it leave by itself, that light was already added
+    fn eval_coat(&self, r_in: &Ray, rec: &HitRecord, frame: &Onb, wo: &Vec3, wi: &Vec3) -> Color {
+        if wi[2] <= 0.0 {
+            return Color::default();
+        }
+        let smooth = self.distrib.effectively_smooth();
+        let mut f = if smooth {
+            Color::default()
+        } else {
+            let (r, _) = self.distrib.dielectric(wo, wi, self.ior);
+            Color::new(r, r, r)
+        };
+        let Some((w_down, g, true)) = self.interface(wo, self.ior) else {
+            return f;
+        };
+
+        // the direction in the coat that leave by wi through a smooth top,
+        // with the fraction transmitted and the change of solid angle
+        let exit = self.inside(wi).filter(|_| smooth).map(|w_up| {
+            let t = 1.0 - fresnel_dielectric(w_up[2], 1.0 / self.ior);
+            let jacobian = wi[2] / (self.ior * self.ior * w_up[2]);
+            (w_up, self.absorption(w_up[2]).mul(t * jacobian))
+        });
+
+        let mut w_down = w_down;
+        let mut throughput = self.absorption(w_down[2]).mul(g);
+        for walk in 0.. {
+            if let Some((w_up, t)) = exit {
+                let r_base = r_in.spawn(rec.point, frame.transform(&w_down));
+                f += throughput * self.inner.eval(&r_base, rec, &frame.transform(&w_up)) * t;
+            }
+            let Some((w_up, att, _)) = self.base(r_in, rec, frame, &w_down) else {
+                break;
+            };
+            throughput = throughput * att;
+            let up = flip(&w_up.mul(-1.0));
+            if !smooth {
+                let (t, _) = self.distrib.dielectric(&up, &flip(wi), 1.0 / self.ior);
+                f += throughput.mul(t);
+            }
+            let Some((wt, g, false)) = self.interface(&up, 1.0 / self.ior) else {
+                break;
+            };
+            w_down = flip(&wt);
+            throughput = throughput.mul(g) * self.absorption(w_down[2]);
+            let Some(t) = survive(walk, throughput) else {
+                break;
+            };
+            throughput = t;
+        }
+        f
+    }
+
+    // aproximation of the pdf of sample, the reflection in the top and one
+    // bounce in the base between the directions of a smooth top, with a bit
+    // of cosine, so any direction that can leave has some pdf
+    fn pdf_coat(&self, r_in: &Ray, rec: &HitRecord, frame: &Onb, wo: &Vec3, wi: &Vec3) -> f64 {
+        if wi[2] <= 0.0 {
+            return 0.0;
+        }
+        let coat = if self.distrib.effectively_smooth() {
+            0.0
+        } else {
+            self.distrib.dielectric(wo, wi, self.ior).1
+        };
+        let n = Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 1.0);
+        let base = match (refract(wo, &n, self.ior), self.inside(wi)) {
+            (Some(w_down), Some(w_up)) => {
+                let r_base = r_in.spawn(rec.point, frame.transform(&w_down));
+                let t = 1.0 - fresnel_dielectric(wo[2], self.ior);
+                let jacobian = wi[2] / (self.ior * self.ior * w_up[2]);
+                t * self.inner.pdf(&r_base, rec, &frame.transform(&w_up)) * jacobian
+            }
+            _ => 0.0,
+        };
+        0.9 * (coat + base) + 0.1 * wi[2] / PI
+    }
+}
+
+impl Material for Layered {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            inner: self.inner.clone_box(),
+            ior: self.ior,
+            distrib: self.distrib,
+            sigma_a: self.sigma_a,
+            thickness: self.thickness,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        self.scatter(r_in, r_ref, rec, attenuation).is_some()
+    }
+    // a smooth coat reflect as a mirror, only that lobe skip the MIS
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> Option<f64> {
+        // the coat is only in the outside
+        if !rec.front_face {
+            return self.inner.scatter(r_in, r_ref, rec, attenuation);
+        }
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        if wo[2] <= 0.0 {
+            return None;
+        }
+        let (wi, weight, delta) = self.sample(r_in, rec, &frame, &wo)?;
+        *r_ref = r_in.spawn(rec.point, frame.transform(&wi));
+        *attenuation = weight;
+        if delta {
+            return Some(0.0);
+        }
+        Some(self.pdf_coat(r_in, rec, &frame, &wo, &wi))
+    }
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.inner.emitted(rec)
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        if !rec.front_face {
+            return self.inner.eval(r_in, rec, wi);
+        }
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        if wo[2] <= 0.0 || self.is_delta(rec) {
+            return Color::default();
+        }
+        let wi = frame.inverse_transform(&wi.unit_vec());
+        self.eval_coat(r_in, rec, &frame, &wo, &wi)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        if !rec.front_face {
+            return self.inner.pdf(r_in, rec, wi);
+        }
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        if wo[2] <= 0.0 || self.is_delta(rec) {
+            return 0.0;
+        }
+        let wi = frame.inverse_transform(&wi.unit_vec());
+        self.pdf_coat(r_in, rec, &frame, &wo, &wi)
+    }
+    fn is_delta(&self, rec: &HitRecord) -> bool {
+        if !rec.front_face {
+            return self.inner.is_delta(rec);
+        }
+        self.distrib.effectively_smooth() && self.inner.is_delta(rec)
+    }
+}
@@ -0,0 +1,802 @@
+use std::{f64::consts::PI, fs, io, path::Path};
+
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    distribution::Distribution1D,
+    lighttree::{LightBounds, LightTree},
+    material::DiffuseLight,
+    onb::Onb,
+    ray::{HitRecord, Hittable, HittableList, Ray, Sphere},
+    utils::{INF, degrees_to_radians, randon_f64},
+    vec::{Vec3, VecTypes},
+};
+
+// light arriving in a point from a light of the scene
+pub struct LightSample {
+    // unit direction from the point to the light
+    pub wi: Vec3,
+    // radiance arriving, already with the distance falloff
+    pub li: Color,
+    // distance to the light, INF for the lights in the infinite
+    pub dist: f64,
+    // pdf in solid angle, 1.0 for the delta lights
+    pub pdf: f64,
+    // normal of the light in the sampled point, zero for the lights without area
+    pub normal: Vec3,
+}
+
+// ray leaving a light, the start of the light paths of the bidirectional
+pub struct LightEmission {
+    pub origin: Vec3,
+    // unit direction of the ray
+    pub dir: Vec3,
+    // normal in the origin, zero for the lights in a point
+    pub normal: Vec3,
+    // radiance for the lights with area, intensity for the point ones
+    pub le: Color,
+    // pdf of the origin in area(1.0 for the points) and of the direction
+    // in solid angle
+    pub pdf_pos: f64,
+    pub pdf_dir: f64,
+}
+
+// direction inside the cone around the unit axis, uniform in solid angle
+fn sample_cone(axis: &Vec3, one_minus_cos: f64) -> Vec3 {
+    let cos = 1.0 - randon_f64() * one_minus_cos;
+    let sin = (1.0 - cos * cos).max(0.0).sqrt();
+    let phi = 2.0 * PI * randon_f64();
+    Onb::new(axis)
+        .transform(&Vec3::new(
+            VecTypes::Coordinates,
+            sin * phi.cos(),
+            sin * phi.sin(),
+            cos,
+        ))
+        .unit_vec()
+}
+
+fn luminance(c: &Color) -> f64 {
+    0.2126 * c.rgb[0] + 0.7152 * c.rgb[1] + 0.0722 * c.rgb[2]
+}
+
+// lights sampled by the next event estimation of the camera, the ones
+// without geometry are never hit by the rays of the materials
+pub trait Light: Send + Sync {
+    fn sample_li(&self, p: &Vec3) -> Option<LightSample>;
+
+    // pdf in solid angle of sample_li choose wi, 0.0 for the delta lights
+    #[allow(unused)]
+    fn pdf_li(&self, p: &Vec3, wi: &Vec3) -> f64 {
+        0.0
+    }
+
+    // only in one direction or point, can't be weighted against the materials
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    // a ray leaving the light, None for the lights in the infinite, that
+    // can only be reached by the paths of the camera
+    fn sample_le(&self) -> Option<LightEmission> {
+        None
+    }
+
+    // pdfs of sample_le choose the origin p and the direction dir(unit),
+    // like in LightEmission
+    #[allow(unused)]
+    fn pdf_le(&self, p: &Vec3, dir: &Vec3) -> (f64, f64) {
+        (0.0, 0.0)
+    }
+
+    // emitted power(luminance), for choose the brighter lights more
+    // the lights in the infinite use the radius of the scene
+    fn power(&self, scene_radius: f64) -> f64;
+
+    // where the light shine, for the light tree, None for the lights in
+    // the infinite that are out of the tree
+    fn bounds(&self) -> Option<LightBounds>;
+}
+
+// light in a point, the intensity is the same in all the directions
+pub struct PointLight {
+    position: Vec3,
+    intensity: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Color) -> Self {
+        Self {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample_li(&self, p: &Vec3) -> Option<LightSample> {
+        let d = self.position - *p;
+        let dist = d.vec_length();
+        if dist <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi: d.div(dist),
+            li: self.intensity.mul(1.0 / (dist * dist)),
+            dist,
+            pdf: 1.0,
+            normal: Vec3::default(),
+        })
+    }
+
+    fn sample_le(&self) -> Option<LightEmission> {
+        Some(LightEmission {
+            origin: self.position,
+            dir: Vec3::random_unit_vec(),
+            normal: Vec3::default(),
+            le: self.intensity,
+            pdf_pos: 1.0,
+            pdf_dir: 1.0 / (4.0 * PI),
+        })
+    }
+
+    fn pdf_le(&self, _p: &Vec3, _dir: &Vec3) -> (f64, f64) {
+        (1.0, 1.0 / (4.0 * PI))
+    }
+
+    fn power(&self, _scene_radius: f64) -> f64 {
+        4.0 * PI * luminance(&self.intensity)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds::omni(
+            Aabb::from_points(self.position, self.position),
+            self.power(0.0),
+        ))
+    }
+}
+
+// light in a point that only shine inside a cone, full inside the inner
+// angle and smooth to zero in the outer angle(both in degrees, from the axis)
+// with a IES profile the measured distribution replace the cone
+pub struct SpotLight {
+    position: Vec3,
+    frame: Onb,
+    intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+    ies: Option<IesProfile>,
+}
+
+impl SpotLight {
+    pub fn new(position: Vec3, direction: Vec3, intensity: Color, inner: f64, outer: f64) -> Self {
+        let outer = outer.clamp(0.0, 180.0);
+        let inner = inner.clamp(0.0, outer);
+        Self {
+            position,
+            frame: Onb::new(&direction),
+            intensity,
+            cos_inner: degrees_to_radians(inner).cos(),
+            cos_outer: degrees_to_radians(outer).cos(),
+            ies: None,
+        }
+    }
+
+    // the intensity is scaled by the profile over its max candela
+    pub fn with_ies(mut self, profile: IesProfile) -> Self {
+        self.ies = Some(profile);
+        self
+    }
+
+    // fraction of the intensity in the direction w(from the light, unit)
+    fn falloff(&self, w: &Vec3) -> f64 {
+        let local = self.frame.inverse_transform(w);
+        if let Some(ies) = &self.ies {
+            let phi = local[1].atan2(local[0]);
+            return ies.value(local[2], phi) / ies.max().max(1e-12);
+        }
+        let cos = local[2];
+        if cos >= self.cos_inner {
+            return 1.0;
+        }
+        if cos <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample_li(&self, p: &Vec3) -> Option<LightSample> {
+        let d = self.position - *p;
+        let dist = d.vec_length();
+        if dist <= 0.0 {
+            return None;
+        }
+        let wi = d.div(dist);
+        let f = self.falloff(&wi.mul(-1.0));
+        if f <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            wi,
+            li: self.intensity.mul(f / (dist * dist)),
+            dist,
+            pdf: 1.0,
+            normal: Vec3::default(),
+        })
+    }
+
+    // uniform in the outer cone, or in all directions with a IES profile
+    fn sample_le(&self) -> Option<LightEmission> {
+        let dir = if self.ies.is_some() {
+            Vec3::random_unit_vec()
+        } else {
+            sample_cone(&self.frame.w, 1.0 - self.cos_outer)
+        };
+        let (pdf_pos, pdf_dir) = self.pdf_le(&self.position, &dir);
+        Some(LightEmission {
+            origin: self.position,
+            dir,
+            normal: Vec3::default(),
+            le: self.intensity.mul(self.falloff(&dir)),
+            pdf_pos,
+            pdf_dir,
+        })
+    }
+
+    fn pdf_le(&self, _p: &Vec3, dir: &Vec3) -> (f64, f64) {
+        if self.ies.is_some() {
+            return (1.0, 1.0 / (4.0 * PI));
+        }
+        if dir.unit_vec().dot(&self.frame.w) < self.cos_outer {
+            return (1.0, 0.0);
+        }
+        (1.0, 1.0 / (2.0 * PI * (1.0 - self.cos_outer)))
+    }
+
+    fn power(&self, _scene_radius: f64) -> f64 {
+        let lum = luminance(&self.intensity);
+        if let Some(ies) = &self.ies {
+            return lum * ies.power() / ies.max().max(1e-12);
+        }
+        // the cone, with the falloff as half of the smooth band
+        let cos = (self.cos_inner + self.cos_outer) / 2.0;
+        2.0 * PI * (1.0 - cos) * lum
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let bbox = Aabb::from_points(self.position, self.position);
+        if self.ies.is_some() {
+            return Some(LightBounds::omni(bbox, self.power(0.0)));
+        }
+        let theta_o = self.cos_inner.clamp(-1.0, 1.0).acos();
+        let theta_e = self.cos_outer.clamp(-1.0, 1.0).acos() - theta_o;
+        Some(LightBounds::new(
+            bbox,
+            self.power(0.0),
+            self.frame.w,
+            theta_o.cos(),
+            theta_e.cos(),
+        ))
+    }
+}
+
+// light of a far source in one direction, like the sun without the disk
+// direction is where the light go, irradiance is in a surface facing it
+pub struct DirectionalLight {
+    direction: Vec3,
+    irradiance: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        Self {
+            direction: direction.unit_vec(),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample_li(&self, _p: &Vec3) -> Option<LightSample> {
+        Some(LightSample {
+            wi: self.direction.mul(-1.0),
+            li: self.irradiance,
+            dist: INF,
+            pdf: 1.0,
+            normal: Vec3::default(),
+        })
+    }
+
+    // all the light crossing the disk of the scene
+    fn power(&self, scene_radius: f64) -> f64 {
+        PI * scene_radius * scene_radius * luminance(&self.irradiance)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+}
+
+// sphere with a uniform radiance in the surface, the outside, it is sampled
+// by the cone of the directions that see it from the point
+pub struct SphereLight {
+    center: Vec3,
+    radius: f64,
+    emit: Color,
+}
+
+impl SphereLight {
+    pub fn new(center: Vec3, radius: f64, emit: Color) -> Self {
+        Self {
+            center,
+            radius: radius.max(0.0),
+            emit,
+        }
+    }
+
+    // the object for the world, it tell the hits what light it is
+    fn geometry(&self, id: usize) -> Box<dyn Hittable + Sync> {
+        Box::new(LightGeometry {
+            obj: Sphere::new(
+                self.center,
+                self.radius,
+                Box::new(DiffuseLight::new(self.emit)),
+            ),
+            id,
+        })
+    }
+
+    // axis to the center and the 1.0 - cos of the cone, None inside
+    fn cone(&self, p: &Vec3) -> Option<(Vec3, f64, f64)> {
+        let d = self.center - *p;
+        let dist2 = d.vec_length().powi(2);
+        let r2 = self.radius * self.radius;
+        if dist2 <= r2 || r2 == 0.0 {
+            return None;
+        }
+        let sin2 = r2 / dist2;
+        // the small cones lose all the precision in the 1.0 - cos
+        let one_minus_cos = if sin2 < 1e-4 {
+            sin2 / 2.0
+        } else {
+            1.0 - (1.0 - sin2).sqrt()
+        };
+        Some((d.div(dist2.sqrt()), dist2.sqrt(), one_minus_cos))
+    }
+}
+
+impl Light for SphereLight {
+    fn sample_li(&self, p: &Vec3) -> Option<LightSample> {
+        let (axis, dist_c, one_minus_cos) = self.cone(p)?;
+        let wi = sample_cone(&axis, one_minus_cos);
+        // nearest hit of the ray with the sphere
+        let b = wi.dot(&axis) * dist_c;
+        let c = dist_c * dist_c - self.radius * self.radius;
+        let dist = b - (b * b - c).max(0.0).sqrt();
+        Some(LightSample {
+            wi,
+            li: self.emit,
+            dist,
+            pdf: 1.0 / (2.0 * PI * one_minus_cos),
+            normal: (*p + wi.mul(dist) - self.center).unit_vec(),
+        })
+    }
+
+    // uniform in the area, cosine around the normal
+    fn sample_le(&self) -> Option<LightEmission> {
+        if self.radius == 0.0 {
+            return None;
+        }
+        let normal = Vec3::random_unit_vec();
+        let local = Onb::new(&normal);
+        let r = randon_f64().sqrt();
+        let phi = 2.0 * PI * randon_f64();
+        let z = (1.0 - r * r).max(0.0).sqrt();
+        let dir = local
+            .transform(&Vec3::new(
+                VecTypes::Coordinates,
+                r * phi.cos(),
+                r * phi.sin(),
+                z,
+            ))
+            .unit_vec();
+        let origin = self.center + normal.mul(self.radius);
+        let (pdf_pos, pdf_dir) = self.pdf_le(&origin, &dir);
+        Some(LightEmission {
+            origin,
+            dir,
+            normal,
+            le: self.emit,
+            pdf_pos,
+            pdf_dir,
+        })
+    }
+
+    fn pdf_le(&self, p: &Vec3, dir: &Vec3) -> (f64, f64) {
+        if self.radius == 0.0 {
+            return (0.0, 0.0);
+        }
+        let normal = (*p - self.center).unit_vec();
+        let cos = normal.dot(&dir.unit_vec()).max(0.0);
+        (1.0 / (4.0 * PI * self.radius * self.radius), cos / PI)
+    }
+
+    fn pdf_li(&self, p: &Vec3, wi: &Vec3) -> f64 {
+        match self.cone(p) {
+            Some((axis, _, one_minus_cos)) if wi.unit_vec().dot(&axis) >= 1.0 - one_minus_cos => {
+                1.0 / (2.0 * PI * one_minus_cos)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    fn power(&self, _scene_radius: f64) -> f64 {
+        PI * 4.0 * PI * self.radius * self.radius * luminance(&self.emit)
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let r = Vec3::new(VecTypes::Coordinates, self.radius, self.radius, self.radius);
+        Some(LightBounds::omni(
+            Aabb::from_points(self.center - r, self.center + r),
+            self.power(0.0),
+        ))
+    }
+}
+
+// the geometry of a light in the world, the hits keep the index of the
+// light, for weight the emission against the next event estimation
+struct LightGeometry {
+    obj: Sphere,
+    id: usize,
+}
+
+impl Hittable for LightGeometry {
+    fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
+        if !self.obj.hit(r, r_max, r_min, rec) {
+            return false;
+        }
+        rec.light = Some(self.id);
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.obj.bounding_box()
+    }
+}
+
+// how the next event estimation choose the light
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightSampling {
+    // all the lights with the same probability
+    #[default]
+    Uniform,
+    // by the emitted power, the same in all the scene
+    Power,
+    // by the light tree, the power with the distance and the orientation
+    // to the point, good for scenes with many lights
+    Tree,
+}
+
+// the lights of the scene, next event estimation choose one by hit
+// build prepare the strategy, the camera call it before render
+#[derive(Default)]
+pub struct LightList {
+    lights: Vec<Box<dyn Light>>,
+    pub strategy: LightSampling,
+    power: Option<Distribution1D>,
+    tree: Option<LightTree>,
+    // lights out of the tree, choosed uniform against the tree
+    infinite: Vec<usize>,
+    // by the power, for start the light paths, without the infinite lights
+    emitters: Option<Distribution1D>,
+}
+
+impl LightList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_strategy(mut self, strategy: LightSampling) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    // return the index of the light
+    pub fn add(&mut self, light: Box<dyn Light>) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    // sphere that shine, in the world and in the lights, so the rays of the
+    // materials can hit it and the next event estimation sample it
+    pub fn add_sphere(&mut self, world: &mut HittableList, center: Vec3, radius: f64, emit: Color) {
+        let light = SphereLight::new(center, radius, emit);
+        world.add(light.geometry(self.lights.len()));
+        self.add(Box::new(light));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&dyn Light> {
+        self.lights.get(id).map(|l| l.as_ref())
+    }
+
+    // the distributions of the strategy, for a scene with that bounds
+    pub fn build(&mut self, scene: &Aabb) {
+        self.power = None;
+        self.tree = None;
+        self.infinite.clear();
+        let radius = (scene.max() - scene.min()).vec_length() / 2.0;
+        let radius = if radius.is_finite() { radius } else { 0.0 };
+        let func: Vec<f64> = self
+            .lights
+            .iter()
+            .map(|l| {
+                if l.bounds().is_some() {
+                    l.power(radius)
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let emitters = Distribution1D::new(func);
+        self.emitters = (emitters.integral() > 0.0).then_some(emitters);
+        match self.strategy {
+            LightSampling::Uniform => {}
+            LightSampling::Power => {
+                let func = self.lights.iter().map(|l| l.power(radius)).collect();
+                self.power = Some(Distribution1D::new(func));
+            }
+            LightSampling::Tree => {
+                let mut bounded = Vec::new();
+                for (i, l) in self.lights.iter().enumerate() {
+                    match l.bounds() {
+                        Some(b) => bounded.push((i, b)),
+                        None => self.infinite.push(i),
+                    }
+                }
+                self.tree = Some(LightTree::new(bounded, self.lights.len()));
+            }
+        }
+    }
+
+    // a light for start a light path, with its index and the probability
+    pub fn choose_emitter(&self) -> Option<(usize, &dyn Light, f64)> {
+        let (i, prob) = self.emitters.as_ref()?.sample_discrete(randon_f64());
+        if prob <= 0.0 {
+            return None;
+        }
+        Some((i, self.lights[i].as_ref(), prob))
+    }
+
+    // probability of choose_emitter choose the light id
+    pub fn emitter_pmf(&self, id: usize) -> f64 {
+        match &self.emitters {
+            Some(d) if id < d.count() => d.pdf(id),
+            _ => 0.0,
+        }
+    }
+
+    // probability of choose the tree and not one of the infinite lights
+    fn tree_prob(&self) -> f64 {
+        match &self.tree {
+            Some(tree) if !tree.is_empty() => 1.0 / (self.infinite.len() + 1) as f64,
+            _ => 0.0,
+        }
+    }
+
+    // a light for the point p, with its index and the probability of choose it
+    pub fn choose(&self, p: &Vec3) -> Option<(usize, &dyn Light, f64)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let n = self.lights.len();
+        let (i, prob) = match (self.strategy, &self.power, &self.tree) {
+            (LightSampling::Power, Some(power), _) => power.sample_discrete(randon_f64()),
+            (LightSampling::Tree, _, Some(tree)) => {
+                let p_tree = self.tree_prob();
+                let u = randon_f64();
+                if u < p_tree {
+                    let (i, prob) = tree.sample(p)?;
+                    (i, prob * p_tree)
+                } else if self.infinite.is_empty() {
+                    // no light with power, the tree is empty too
+                    return None;
+                } else {
+                    let k = ((u - p_tree) / (1.0 - p_tree) * self.infinite.len() as f64) as usize;
+                    (
+                        self.infinite[k.min(self.infinite.len() - 1)],
+                        (1.0 - p_tree) / self.infinite.len() as f64,
+                    )
+                }
+            }
+            // uniform, also when build was not called
+            _ => (
+                ((randon_f64() * n as f64) as usize).min(n - 1),
+                1.0 / n as f64,
+            ),
+        };
+        if prob <= 0.0 {
+            return None;
+        }
+        Some((i, self.lights[i].as_ref(), prob))
+    }
+
+    // probability of choose the light id for the point p
+    pub fn pmf(&self, id: usize, p: &Vec3) -> f64 {
+        if id >= self.lights.len() {
+            return 0.0;
+        }
+        match (self.strategy, &self.power, &self.tree) {
+            (LightSampling::Power, Some(power), _) => power.pdf(id),
+            (LightSampling::Tree, _, Some(tree)) => {
+                if self.infinite.contains(&id) {
+                    (1.0 - self.tree_prob()) / self.infinite.len().max(1) as f64
+                } else {
+                    tree.pmf(id, p) * self.tree_prob()
+                }
+            }
+            _ => 1.0 / self.lights.len() as f64,
+        }
+    }
+}
+
+fn bad_ies(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// photometric data of a fixture, IES LM-63 with type C photometry
+// the vertical angle is from the axis of the light(nadir), the horizontal
+// around it, values in candela
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    // candela[h][v]
+    candela: Vec<Vec<f64>>,
+    max: f64,
+}
+
+impl IesProfile {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        // the keywords are before the TILT line, the numbers after it
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .find(|l| l.trim_start().starts_with("TILT="))
+            .ok_or_else(|| bad_ies("no TILT line"))?;
+        if tilt.trim() != "TILT=NONE" {
+            return Err(bad_ies("only TILT=NONE is supported"));
+        }
+        let nums = lines
+            .flat_map(|l| l.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<f64>().map_err(|_| bad_ies("bad number")))
+            .collect::<io::Result<Vec<f64>>>()?;
+        if nums.len() < 13 {
+            return Err(bad_ies("short header"));
+        }
+
+        let multiplier = nums[2];
+        let n_v = nums[3] as usize;
+        let n_h = nums[4] as usize;
+        if nums[5] as i64 != 1 {
+            return Err(bad_ies("only type C photometry is supported"));
+        }
+        // 10 numbers of the header and 3 of the ballast and watts
+        let data = &nums[13..];
+        if n_v == 0 || n_h == 0 || data.len() < n_v + n_h + n_v * n_h {
+            return Err(bad_ies("missing angles or candela values"));
+        }
+
+        let vertical = data[..n_v].to_vec();
+        let horizontal = data[n_v..n_v + n_h].to_vec();
+        let candela: Vec<Vec<f64>> = (0..n_h)
+            .map(|h| {
+                let start = n_v + n_h + h * n_v;
+                data[start..start + n_v]
+                    .iter()
+                    .map(|c| c * multiplier)
+                    .collect()
+            })
+            .collect();
+        let max = candela.iter().flatten().cloned().fold(0.0, f64::max);
+        Ok(IesProfile {
+            vertical,
+            horizontal,
+            candela,
+            max,
+        })
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    // candela in the direction with cos from the axis and the azimuth phi
+    pub fn value(&self, cos_theta: f64, phi: f64) -> f64 {
+        let theta = cos_theta.clamp(-1.0, 1.0).acos().to_degrees();
+        let mut h = phi.to_degrees().rem_euclid(360.0);
+        // the symmetries of the type C, by the last horizontal angle
+        let last = *self.horizontal.last().unwrap_or(&0.0);
+        if last <= 0.0 {
+            h = 0.0;
+        } else if last <= 90.0 {
+            h = match h {
+                h if h <= 90.0 => h,
+                h if h <= 180.0 => 180.0 - h,
+                h if h <= 270.0 => h - 180.0,
+                h => 360.0 - h,
+            };
+        } else if last <= 180.0 && h > 180.0 {
+            h = 360.0 - h;
+        }
+
+        let (hi, ht) = lerp_index(&self.horizontal, h);
+        let (vi, vt) = lerp_index(&self.vertical, theta);
+        let at = |i: usize| {
+            let row = &self.candela[i];
+            if vi + 1 < row.len() {
+                row[vi] * (1.0 - vt) + row[vi + 1] * vt
+            } else {
+                row[vi]
+            }
+        };
+        let c = if hi + 1 < self.candela.len() {
+            at(hi) * (1.0 - ht) + at(hi + 1) * ht
+        } else {
+            at(hi)
+        };
+        // out of the measured vertical angles there is no light
+        if theta > *self.vertical.last().unwrap_or(&180.0) {
+            return 0.0;
+        }
+        c.max(0.0)
+    }
+
+    // total flux, integrating the candela over the sphere
+    pub fn power(&self) -> f64 {
+        let steps = 64;
+        let mut accum = 0.0;
+        for i in 0..steps {
+            for j in 0..steps {
+                let cos = 1.0 - 2.0 * (i as f64 + 0.5) / steps as f64;
+                let phi = 2.0 * PI * (j as f64 + 0.5) / steps as f64;
+                accum += self.value(cos, phi);
+            }
+        }
+        accum * 4.0 * PI / (steps * steps) as f64
+    }
+}
+
+// index of the segment of the sorted values with x, and the position in it
+fn lerp_index(values: &[f64], x: f64) -> (usize, f64) {
+    if values.len() < 2 || x <= values[0] {
+        return (0, 0.0);
+    }
+    let i = values.partition_point(|v| *v <= x).min(values.len() - 1);
+    let i = i.saturating_sub(1);
+    if i + 1 >= values.len() {
+        return (values.len() - 1, 0.0);
+    }
+    let w = values[i + 1] - values[i];
+    let t = if w > 0.0 { (x - values[i]) / w } else { 0.0 };
+    (i, t.clamp(0.0, 1.0))
+}
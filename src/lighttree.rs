@@ -0,0 +1,292 @@
+use std::f64::consts::PI;
+
+use crate::{
+    aabb::Aabb,
+    utils::randon_f64,
+    vec::{Vec3, VecTypes},
+};
+
+// cos(a - b), with the angles given by sin and cos, clamped to 1.0 when a < b
+fn cos_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_b
+}
+
+// sin(a - b), clamped to 0.0 when a < b
+fn sin_sub_clamped(sin_a: f64, cos_a: f64, sin_b: f64, cos_b: f64) -> f64 {
+    if cos_a > cos_b {
+        return 0.0;
+    }
+    sin_a * cos_b - cos_a * sin_b
+}
+
+fn sin_from_cos(cos: f64) -> f64 {
+    (1.0 - cos * cos).max(0.0).sqrt()
+}
+
+// rotate v by the angle around the unit axis(Rodrigues)
+fn rotate(v: &Vec3, axis: &Vec3, angle: f64) -> Vec3 {
+    let (s, c) = angle.sin_cos();
+    v.mul(c) + (*axis * *v).mul(s) + axis.mul(axis.dot(v) * (1.0 - c))
+}
+
+// where and how a light emit, to guess its importance to a point without
+// sample it, like in pbrt-v4
+// the light go out in the directions inside the cone w, theta_o, plus
+// theta_e around it where the emission fall to zero
+#[derive(Debug, Clone, Copy)]
+pub struct LightBounds {
+    pub bbox: Aabb,
+    pub phi: f64,
+    pub w: Vec3,
+    pub cos_theta_o: f64,
+    pub cos_theta_e: f64,
+}
+
+impl LightBounds {
+    pub fn new(bbox: Aabb, phi: f64, w: Vec3, cos_theta_o: f64, cos_theta_e: f64) -> Self {
+        Self {
+            bbox,
+            phi,
+            w: w.unit_vec(),
+            cos_theta_o,
+            cos_theta_e,
+        }
+    }
+
+    // light in all the directions, like a point or a sphere
+    pub fn omni(bbox: Aabb, phi: f64) -> Self {
+        Self::new(
+            bbox,
+            phi,
+            Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 1.0),
+            -1.0,
+            0.0,
+        )
+    }
+
+    fn union(a: &Self, b: &Self) -> Self {
+        if a.phi == 0.0 {
+            return *b;
+        }
+        if b.phi == 0.0 {
+            return *a;
+        }
+        let (w, cos_theta_o) = cone_union(a.w, a.cos_theta_o, b.w, b.cos_theta_o);
+        LightBounds {
+            bbox: Aabb::union(&a.bbox, &b.bbox),
+            phi: a.phi + b.phi,
+            w,
+            cos_theta_o,
+            cos_theta_e: a.cos_theta_e.min(b.cos_theta_e),
+        }
+    }
+
+    // guess of the light arriving in p, 0.0 if the cone can't reach it
+    pub fn importance(&self, p: &Vec3) -> f64 {
+        let pc = self.bbox.centroid();
+        let radius = (self.bbox.max() - self.bbox.min()).vec_length() / 2.0;
+        let d = *p - pc;
+        let dist2 = d.vec_length().powi(2);
+        // not too near, the lights are not points
+        let d2 = dist2.max(radius);
+
+        let cos_theta_w = if dist2 > 0.0 {
+            self.w.dot(&d.div(dist2.sqrt()))
+        } else {
+            1.0
+        };
+        let sin_theta_w = sin_from_cos(cos_theta_w);
+
+        // the box seen from p, all the directions if p is inside
+        let cos_theta_b = if dist2 < radius * radius {
+            -1.0
+        } else {
+            (1.0 - radius * radius / dist2).max(0.0).sqrt()
+        };
+        let sin_theta_b = sin_from_cos(cos_theta_b);
+
+        // the angle to p out of the cone and of the box, minimum possible
+        let sin_theta_o = sin_from_cos(self.cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+        self.phi * cos_theta_p / d2
+    }
+}
+
+// smallest cone with the two cones inside
+fn cone_union(wa: Vec3, cos_a: f64, wb: Vec3, cos_b: f64) -> (Vec3, f64) {
+    let theta_a = cos_a.clamp(-1.0, 1.0).acos();
+    let theta_b = cos_b.clamp(-1.0, 1.0).acos();
+    let theta_d = wa.dot(&wb).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (wa, cos_a);
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return (wb, cos_b);
+    }
+    let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+    if theta_o >= PI {
+        return (wa, -1.0);
+    }
+    let axis = wa * wb;
+    if axis.near_zero() {
+        return (wa, -1.0);
+    }
+    let w = rotate(&wa, &axis.unit_vec(), theta_o - theta_a);
+    (w.unit_vec(), theta_o.cos())
+}
+
+enum Node {
+    Leaf {
+        light: usize,
+        bounds: LightBounds,
+    },
+    Interior {
+        left: Box<Node>,
+        right: Box<Node>,
+        bounds: LightBounds,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+// binary tree of the lights with bounds, each step choose a child by its
+// importance to the point, so the near and bright lights are choosed more
+// and the far or turned away ones are almost never sampled
+pub struct LightTree {
+    root: Option<Node>,
+    // the left(false) and right(true) steps from the root to each light
+    paths: Vec<Option<Vec<bool>>>,
+}
+
+impl LightTree {
+    // lights is (id, bounds), the ids are the index in the light list
+    pub fn new(lights: Vec<(usize, LightBounds)>, n_ids: usize) -> Self {
+        let mut paths = vec![None; n_ids];
+        let lights: Vec<_> = lights.into_iter().filter(|(_, b)| b.phi > 0.0).collect();
+        let root = if lights.is_empty() {
+            None
+        } else {
+            Some(Self::build(lights, &mut Vec::new(), &mut paths))
+        };
+        LightTree { root, paths }
+    }
+
+    fn build(
+        mut lights: Vec<(usize, LightBounds)>,
+        path: &mut Vec<bool>,
+        paths: &mut Vec<Option<Vec<bool>>>,
+    ) -> Node {
+        if lights.len() == 1 {
+            let (light, bounds) = lights[0];
+            paths[light] = Some(path.clone());
+            return Node::Leaf { light, bounds };
+        }
+
+        // split in the median of the biggest axis of the centroids
+        let centroids = lights.iter().fold(Aabb::default(), |acc, (_, b)| {
+            Aabb::union(
+                &acc,
+                &Aabb::from_points(b.bbox.centroid(), b.bbox.centroid()),
+            )
+        });
+        let size = centroids.max() - centroids.min();
+        let axis = (0..3)
+            .max_by(|a, b| size[*a].total_cmp(&size[*b]))
+            .unwrap_or(0);
+        lights.sort_by(|a, b| a.1.bbox.centroid()[axis].total_cmp(&b.1.bbox.centroid()[axis]));
+        let right_lights = lights.split_off(lights.len() / 2);
+
+        path.push(false);
+        let left = Self::build(lights, path, paths);
+        path.pop();
+        path.push(true);
+        let right = Self::build(right_lights, path, paths);
+        path.pop();
+
+        let bounds = LightBounds::union(left.bounds(), right.bounds());
+        Node::Interior {
+            left: Box::new(left),
+            right: Box::new(right),
+            bounds,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    // a light for the point p, and the probability of choose it
+    pub fn sample(&self, p: &Vec3) -> Option<(usize, f64)> {
+        let mut node = self.root.as_ref()?;
+        let mut pmf = 1.0;
+        loop {
+            match node {
+                Node::Leaf { light, bounds } => {
+                    // a lone light can still be unable to reach p
+                    if bounds.importance(p) <= 0.0 {
+                        return None;
+                    }
+                    return Some((*light, pmf));
+                }
+                Node::Interior { left, right, .. } => {
+                    let il = left.bounds().importance(p);
+                    let ir = right.bounds().importance(p);
+                    if il + ir <= 0.0 {
+                        return None;
+                    }
+                    let pl = il / (il + ir);
+                    if randon_f64() < pl {
+                        pmf *= pl;
+                        node = left;
+                    } else {
+                        pmf *= 1.0 - pl;
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    // probability of sample choose the light for the point p
+    pub fn pmf(&self, light: usize, p: &Vec3) -> f64 {
+        let (Some(mut node), Some(Some(path))) = (self.root.as_ref(), self.paths.get(light)) else {
+            return 0.0;
+        };
+        let mut pmf = 1.0;
+        for go_right in path {
+            let Node::Interior { left, right, .. } = node else {
+                return 0.0;
+            };
+            let il = left.bounds().importance(p);
+            let ir = right.bounds().importance(p);
+            if il + ir <= 0.0 {
+                return 0.0;
+            }
+            if *go_right {
+                pmf *= ir / (il + ir);
+                node = right;
+            } else {
+                pmf *= il / (il + ir);
+                node = left;
+            }
+        }
+        if node.bounds().importance(p) <= 0.0 {
+            return 0.0;
+        }
+        pmf
+    }
+}
@@ -1,20 +1,129 @@
+mod aabb;
+mod bdpt;
+mod bump;
 mod camera;
+mod checkpoint;
 mod color;
+mod cutout;
+mod distribution;
+mod environment;
+mod film;
+mod instance;
+mod layered;
+mod light;
+mod lighttree;
 mod material;
+mod medium;
+mod microfacet;
+mod onb;
+mod perlin;
+mod phase;
+mod photon;
+mod principled;
+mod quad;
 mod ray;
+mod sky;
+mod spectrum;
+mod subsurface;
+mod texture;
+mod thinfilm;
+mod tile;
+mod triangle;
 mod utils;
 mod vec;
+mod volume;
 
-use std::{f64::consts::PI, fs::File};
+use std::{env, error::Error, f64::consts::PI, fs::File, path::Path, sync::Arc, time::Duration};
 
+use aabb::Aabb;
+use bump::{BumpMap, NormalMap};
+use camera::{Integrator, RenderMode};
+use checkpoint::Checkpoint;
 use color::Color;
-use image::{Delay, Frame, ImageBuffer, RgbImage, Rgba, RgbaImage, codecs::gif::GifEncoder};
-use material::{Dieletric, Lambertian, Material, Metal};
+use cutout::Cutout;
+use environment::EnvironmentMap;
+use film::Progressive;
+use image::{
+    Delay, Frame, ImageBuffer, Rgb, Rgb32FImage, RgbImage, Rgba, RgbaImage, codecs::gif::GifEncoder,
+};
+use instance::Instance;
+use layered::Layered;
+use light::{DirectionalLight, IesProfile, LightList, LightSampling, PointLight, SpotLight};
+use material::{
+    Conductor, Dieletric, DiffuseLight, Dispersion, DispersiveDieletric, Lambertian, Material,
+    Metal, MixMaterial, OrenNayar, RoughDieletric,
+};
+use medium::{ConstantMedium, Fog};
+use phase::{DoubleHenyeyGreenstein, HenyeyGreenstein};
+use photon::PhotonSettings;
+use principled::Principled;
+use quad::Quad;
 use ray::{HittableList, Sphere};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use sky::Sky;
+use spectrum::Illuminant;
+use subsurface::Subsurface;
+use texture::{CheckerTexture, ImageTexture, NoiseTexture, UvChecker, constant, solid};
+use thinfilm::ThinFilm;
+use tile::{Region, TileOrder};
+use triangle::Triangle;
 use vec::*;
+use volume::{DensityGrid, GridMedium};
 
+// the scene by the first argument, the files by name=path after it, like
+//   cargo run --release -- materials hdri=studio.hdr normal=bricks.png
+// without scene the empty world is rendered
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result: Result<(), Box<dyn Error>> = match args.first().map(String::as_str) {
+        None => {
+            empty();
+            Ok(())
+        }
+        Some("materials") => materials(&args),
+        Some("fixtures") => fixtures(&args),
+        Some("volumes") => volumes(&args),
+        Some("progressive") => progressive(&args),
+        Some("caustics") => {
+            caustics();
+            Ok(())
+        }
+        Some("gif1") => {
+            _gif_part_1();
+            Ok(())
+        }
+        Some("gif2") => {
+            _gif_part_2();
+            Ok(())
+        }
+        Some("lights") => {
+            _light_sampling();
+            Ok(())
+        }
+        Some(other) => Err(format!("cena desconhecida {}", other).into()),
+    };
+    if let Err(e) = result {
+        println!("ERro na cena {:?}", e);
+    }
+}
+
+// the value of a name=path argument
+fn arg<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .find_map(|a| a.strip_prefix(name)?.strip_prefix('='))
+}
+
+fn coord(x: f64, y: f64, z: f64) -> Vec3 {
+    Vec3::new(VecTypes::Coordinates, x, y, z)
+}
+
+fn save(i: &RgbImage, name: &str) {
+    if let Err(e) = i.save(name) {
+        println!("ERro ao salvar image {:?}", e);
+    }
+}
+
+fn empty() {
     let world = HittableList::new();
     let asp: f64 = 16.0 / 9.0;
     let mut c = camera::Camera::new(asp, 300);
@@ -99,3 +208,525 @@ fn _gif_part_1() {
     let mut r = GifEncoder::new(f);
     let _ = r.encode_frames(fs);
 }
+
+// same orbit of _gif_part_1, but with the spheres moving while the shutter is open
+// the orbit is a Instance rotating arround y, so each frame have motion blur
+fn _gif_part_2() {
+    let frames = 100;
+    let fs: Vec<Frame> = (0..frames)
+        .into_par_iter()
+        .map(|p| {
+            let mut world = HittableList::new();
+            let m_g = Lambertian::new(Color::new(1.0, 0.3, 0.5));
+            let g = Sphere::new(
+                Vec3::new(VecTypes::Coordinates, 0.0, -103.0, 0.0),
+                100.0,
+                m_g.clone_box(),
+            );
+            world.add(g.boxed());
+
+            let m = Dieletric::new(1.5);
+            let s = Sphere::new(
+                Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 0.0),
+                1.0,
+                m.clone_box(),
+            );
+            world.add(s.boxed());
+
+            let mut orbit = HittableList::new();
+            let v = Vec3::new(VecTypes::Coordinates, 2.0, 0.0, 0.0);
+            let m2 = Lambertian::new(Color::new(0.81, 0.23, 0.12));
+            orbit.add(Sphere::new(v.mul(-1.0), 0.7, m2.clone_box()).boxed());
+            let m3 = Metal::new(Color::new(0.3, 0.25, 0.87), 0.0);
+            orbit.add(Sphere::new(v, 0.7, m3.clone_box()).boxed());
+
+            // angle of this frame and of the next one
+            let step = 360.0 / frames as f64;
+            let angle = -step * p as f64;
+            let o = Vec3::default();
+            world.add(Instance::new_moving(Box::new(orbit), o, o, angle, angle - step).boxed());
+
+            let asp: f64 = 16.0 / 9.0;
+            let mut c = camera::Camera::new(asp, 1200);
+            c.lookfrom = Vec3::new(VecTypes::Coordinates, 5.0, 2.0, 3.0);
+            c.lookat = Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 0.0);
+            c.vup = Vec3::new(VecTypes::Coordinates, 0.0, 1.0, 0.0);
+            c.vfov = 60.0;
+            // 180 degree shutter, open in half of the frame
+            c.shutter_open = 0.0;
+            c.shutter_close = 0.5;
+            let i: RgbImage = c.render(&world);
+            let a = _rgb_rgba(&i);
+            let d = Delay::from_numer_denom_ms(100, 1);
+
+            Frame::from_parts(a, 0, 0, d)
+        })
+        .collect();
+    let f = File::create("test_blur.gif").unwrap();
+    let mut r = GifEncoder::new(f);
+    let _ = r.encode_frames(fs);
+}
+
+// room lit by a grid of 144 small lights of diferent colors, rendered with
+// the same samples by each strategy of choose a light, the tree is less noisy
+fn _light_sampling() {
+    let strategies = [
+        ("uniform", LightSampling::Uniform),
+        ("power", LightSampling::Power),
+        ("tree", LightSampling::Tree),
+    ];
+    for (name, strategy) in strategies {
+        let mut world = HittableList::new();
+        let floor = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let g = Sphere::new(
+            Vec3::new(VecTypes::Coordinates, 0.0, -1000.0, 0.0),
+            1000.0,
+            floor.clone_box(),
+        );
+        world.add(g.boxed());
+        // closed dark dome, no light of the sky
+        let dome = Sphere::new(Vec3::default(), 30.0, floor.clone_box());
+        world.add(dome.boxed());
+        let m = Metal::new(Color::new(0.8, 0.8, 0.8), 0.2);
+        let s = Sphere::new(
+            Vec3::new(VecTypes::Coordinates, 0.0, 1.0, 0.0),
+            1.0,
+            m.clone_box(),
+        );
+        world.add(s.boxed());
+
+        let asp: f64 = 16.0 / 9.0;
+        let mut c = camera::Camera::new(asp, 400);
+        c.samples_per_pixel = 16;
+        c.lookfrom = Vec3::new(VecTypes::Coordinates, 0.0, 3.0, 9.0);
+        c.lookat = Vec3::new(VecTypes::Coordinates, 0.0, 0.5, 0.0);
+        c.vup = Vec3::new(VecTypes::Coordinates, 0.0, 1.0, 0.0);
+        c.vfov = 50.0;
+        c.lights = LightList::default().with_strategy(strategy);
+        for i in 0..12 {
+            for j in 0..12 {
+                let center = Vec3::new(
+                    VecTypes::Coordinates,
+                    (i as f64 - 5.5) * 1.5,
+                    0.3,
+                    (j as f64 - 5.5) * 1.5,
+                );
+                // a few bright ones between many weak ones
+                let power = if (i + j) % 7 == 0 { 400.0 } else { 20.0 };
+                let color = Color::new(
+                    power * (0.3 + 0.7 * i as f64 / 11.0),
+                    power * 0.6,
+                    power * (0.3 + 0.7 * j as f64 / 11.0),
+                );
+                c.lights.add_sphere(&mut world, center, 0.1, color);
+            }
+        }
+
+        let start = std::time::Instant::now();
+        let i: RgbImage = c.render(&world);
+        println!("{}: {:?}", name, start.elapsed());
+        if let Err(e) = i.save(format!("lights_{}.png", name)) {
+            println!("ERro ao salvar image {:?}", e);
+        }
+    }
+}
+
+// the 6 faces of the box between the corners a and b
+fn quad_box(a: Vec3, b: Vec3, mat: &dyn Material) -> HittableList {
+    let min = coord(a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2]));
+    let max = coord(a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2]));
+    let dx = coord(max[0] - min[0], 0.0, 0.0);
+    let dy = coord(0.0, max[1] - min[1], 0.0);
+    let dz = coord(0.0, 0.0, max[2] - min[2]);
+    let mut sides = HittableList::new();
+    let faces = [
+        (coord(min[0], min[1], max[2]), dx, dy),
+        (coord(max[0], min[1], max[2]), dz.mul(-1.0), dy),
+        (coord(max[0], min[1], min[2]), dx.mul(-1.0), dy),
+        (coord(min[0], min[1], min[2]), dz, dy),
+        (coord(min[0], max[1], max[2]), dx, dz.mul(-1.0)),
+        (coord(min[0], min[1], min[2]), dx, dz),
+    ];
+    for (q, u, v) in faces {
+        sides.add(Quad::new(q, u, v, mat.clone_box()).boxed());
+    }
+    sides
+}
+
+// normal map of circular waves, in the tangent space of the texture
+fn ripples(size: u32) -> RgbImage {
+    ImageBuffer::from_fn(size, size, |x, y| {
+        let u = x as f64 / size as f64 - 0.5;
+        let v = y as f64 / size as f64 - 0.5;
+        let r = (u * u + v * v).sqrt().max(1e-6);
+        let slope = 0.5 * (r * 60.0).cos();
+        let (nx, ny) = (slope * u / r, slope * v / r);
+        let nz = (1.0 - nx * nx - ny * ny).max(0.0).sqrt();
+        let c = |n: f64| ((n * 0.5 + 0.5) * 255.0) as u8;
+        Rgb([c(nx), c(ny), c(nz)])
+    })
+}
+
+// a row of the materials over a checker floor, lit by the sky
+// hdri= is the environment, texture= the wall and normal= the map of a sphere
+fn materials(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut world = HittableList::new();
+    let mut floor = Principled::new(Color::default());
+    floor.base_color = Arc::new(CheckerTexture::new(
+        0.5,
+        solid(Color::new(0.8, 0.8, 0.8)),
+        solid(Color::new(0.2, 0.3, 0.1)),
+    ));
+    floor.roughness = constant(0.8);
+    world.add(Sphere::new(coord(0.0, -1000.0, 0.0), 1000.0, Box::new(floor)).boxed());
+
+    let mut wall = Principled::new(Color::default());
+    wall.base_color = match arg(args, "texture") {
+        Some(path) => Arc::new(ImageTexture::load(path)?),
+        None => Arc::new(UvChecker::new(
+            16.0,
+            solid(Color::new(0.6, 0.6, 0.6)),
+            solid(Color::new(0.3, 0.3, 0.4)),
+        )),
+    };
+    let wall = Quad::new(
+        coord(-8.0, 0.0, -3.0),
+        coord(16.0, 0.0, 0.0),
+        coord(0.0, 5.0, 0.0),
+        Box::new(wall),
+    );
+    world.add(wall.boxed());
+
+    // a sail with its own uv and a fence, the holes of the checker let the light pass
+    let sail = Triangle::new(
+        coord(-6.5, 0.0, 1.5),
+        coord(-5.0, 0.0, 1.5),
+        coord(-5.75, 1.8, 1.5),
+        Box::new(Principled::new(Color::new(0.9, 0.8, 0.3))),
+    )
+    .with_uvs([(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)]);
+    world.add(sail.boxed());
+    let fence = Quad::new(
+        coord(5.0, 0.0, 1.5),
+        coord(1.5, 0.0, 0.0),
+        coord(0.0, 1.5, 0.0),
+        Box::new(Lambertian::new(Color::new(0.5, 0.3, 0.1))),
+    );
+    let holes = UvChecker::new(6.0, constant(1.0), constant(0.0));
+    world.add(Cutout::new(fence.boxed(), Arc::new(holes)).boxed());
+
+    let noise: texture::TextureRef = Arc::new(NoiseTexture::new(4.0));
+    // rust over the steel, in the dark parts of the noise
+    let rusty = MixMaterial::with_mask(
+        Box::new(Metal::new(Color::new(0.7, 0.7, 0.7), 0.1)),
+        Box::new(OrenNayar::new(Color::new(0.4, 0.15, 0.05), 20.0)),
+        noise.clone(),
+    );
+    // a glaze, a bit of mirror over the clay
+    let glazed = MixMaterial::new(
+        Box::new(Lambertian::new(Color::new(0.3, 0.5, 0.7))),
+        Box::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.0)),
+        0.2,
+    );
+    let mut plastic = Principled::new(Color::default());
+    plastic.base_color = Arc::new(UvChecker::new(
+        8.0,
+        solid(Color::new(0.8, 0.1, 0.1)),
+        solid(Color::new(0.9, 0.9, 0.9)),
+    ));
+    plastic.clearcoat = constant(1.0);
+    plastic.sheen = constant(0.5);
+    let aluminum = Box::new(Conductor::aluminum(0.2));
+    let hammered = match arg(args, "normal") {
+        Some(path) => NormalMap::load(aluminum, path)?,
+        None => NormalMap::new(aluminum, Arc::new(ImageTexture::from_image(ripples(256)))),
+    }
+    .with_strength(0.8);
+    let paint = Layered::new(
+        Box::new(Lambertian::new(Color::new(0.6, 0.05, 0.05))),
+        1.5,
+        0.1,
+    )
+    .with_absorption(Color::new(0.5, 1.0, 2.0), 0.1);
+    let oiled = ThinFilm::textured(noise.clone(), 250.0, 600.0, 1.5);
+    let bubble = Dieletric::new_with_coefficient(1.33, Color::default())
+        .with_film(ThinFilm::new(400.0, 1.33));
+    let mats: Vec<Box<dyn Material>> = vec![
+        Box::new(Conductor::gold(0.2)),
+        Box::new(Conductor::copper(0.1)),
+        Box::new(Conductor::silver(0.0).with_film(oiled)),
+        Box::new(hammered),
+        Box::new(Dieletric::new_absorbing(
+            1.5,
+            Color::new(0.2, 0.6, 0.3),
+            1.0,
+        )),
+        Box::new(bubble),
+        Box::new(RoughDieletric::new(1.5, 0.3)),
+        Box::new(rusty),
+        Box::new(glazed),
+        Box::new(plastic),
+        Box::new(paint),
+        Box::new(BumpMap::new(
+            Box::new(Lambertian::new(Color::new(0.7, 0.6, 0.5))),
+            noise,
+            0.05,
+        )),
+    ];
+    let n = mats.len();
+    for (i, m) in mats.into_iter().enumerate() {
+        let x = (i as f64 - (n - 1) as f64 / 2.0) * 1.1;
+        world.add(Sphere::new(coord(x, 0.5, 0.0), 0.5, m).boxed());
+    }
+
+    let mut c = camera::Camera::new(16.0 / 9.0, 800);
+    c.samples_per_pixel = 64;
+    c.lookfrom = coord(0.0, 2.0, 9.0);
+    c.lookat = coord(0.0, 0.5, 0.0);
+    c.vup = coord(0.0, 1.0, 0.0);
+    c.vfov = 50.0;
+    let env: Arc<dyn environment::Environment> = match arg(args, "hdri") {
+        Some(path) => Arc::new(EnvironmentMap::load(path)?.with_rotation(90.0)),
+        None => Arc::new(
+            Sky::new(35.0, 120.0, 3.0)
+                .with_sun_size(1.0)
+                .with_intensity(0.8),
+        ),
+    };
+    c.environment = Some(env);
+    save(&c.render(&world), "materials.png");
+    Ok(())
+}
+
+// a point and a narrow spot, with 5 vertical angles
+const SPOT_IES: &str = "IESNA:LM-63-2002
+TILT=NONE
+1 -1 1 5 1 1 2 0 0 0
+1 1 0
+0 10 20 30 90
+0
+1000 900 500 100 0
+";
+
+// a dark room lit only by the fixtures, a point, a spot(with the profile of
+// ies= or a narrow one) and a low sun as directional light
+// the cube is rotated and the ball cross the frame while the shutter is open
+fn fixtures(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut world = HittableList::new();
+    let white = Lambertian::new(Color::new(0.7, 0.7, 0.7));
+    world.add(Sphere::new(coord(0.0, -1000.0, 0.0), 1000.0, white.clone_box()).boxed());
+    let blue = Lambertian::new(Color::new(0.2, 0.4, 0.8));
+    let cube = quad_box(coord(-0.5, 0.0, -0.5), coord(0.5, 1.0, 0.5), &blue);
+    world.add(Instance::new(Box::new(cube), coord(-1.5, 0.0, 0.0), 30.0).boxed());
+    let ball = Sphere::new_moving(
+        coord(1.0, 0.5, 0.5),
+        coord(1.6, 0.5, 0.5),
+        0.5,
+        Box::new(Conductor::gold(0.3)),
+    );
+    world.add(ball.boxed());
+
+    let mut c = camera::Camera::new(16.0 / 9.0, 600);
+    c.samples_per_pixel = 64;
+    c.lookfrom = coord(0.0, 2.0, 6.0);
+    c.lookat = coord(0.0, 0.5, 0.0);
+    c.vup = coord(0.0, 1.0, 0.0);
+    c.vfov = 45.0;
+    c.shutter_open = 0.0;
+    c.shutter_close = 1.0;
+    let night = Rgb32FImage::from_pixel(4, 2, Rgb([0.02, 0.02, 0.03]));
+    c.environment = Some(Arc::new(
+        EnvironmentMap::from_image(night).with_intensity(0.5),
+    ));
+    let profile = match arg(args, "ies") {
+        Some(path) => IesProfile::load(path)?,
+        None => IesProfile::parse(SPOT_IES)?,
+    };
+    c.lights = LightList::new();
+    c.lights.add(Box::new(PointLight::new(
+        coord(0.0, 3.0, 2.0),
+        Color::new(5.0, 4.0, 3.0),
+    )));
+    let spot = SpotLight::new(
+        coord(1.5, 4.0, 0.0),
+        coord(-0.2, -1.0, 0.0),
+        Color::new(20.0, 20.0, 20.0),
+        15.0,
+        30.0,
+    );
+    c.lights.add(Box::new(spot.with_ies(profile)));
+    c.lights.add(Box::new(DirectionalLight::new(
+        coord(-1.0, -0.3, -0.5),
+        Color::new(0.6, 0.4, 0.2),
+    )));
+    save(&c.render(&world), "fixtures.png");
+    Ok(())
+}
+
+// smoke, fire and two translucent balls under a F11 lamp, in spectral
+// grid= is the smoke, a grid in text or with .raw grid_size³ floats
+fn volumes(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut world = HittableList::new();
+    let white = Lambertian::new(Color::new(0.7, 0.7, 0.7));
+    world.add(Sphere::new(coord(0.0, -1000.0, 0.0), 1000.0, white.clone_box()).boxed());
+
+    let smoke = match arg(args, "grid") {
+        Some(path) if Path::new(path).extension().is_some_and(|e| e == "raw") => {
+            let n = arg(args, "grid_size").map_or(Ok(64), str::parse)?;
+            DensityGrid::load_raw(path, n, n, n)?
+        }
+        Some(path) => DensityGrid::load_text(path)?,
+        None => DensityGrid::from_perlin(32, 4.0),
+    };
+    let smoke_box = Aabb::from_points(coord(-2.5, 0.0, -1.0), coord(-0.5, 2.0, 1.0));
+    let phase = DoubleHenyeyGreenstein::new(0.6, -0.3, 0.8);
+    let grey = Color::new(0.8, 0.8, 0.8);
+    world.add(GridMedium::new_with_phase(smoke, smoke_box, 5.0, grey, Box::new(phase)).boxed());
+    // the fire emit where the same noise absorb
+    let fire_box = Aabb::from_points(coord(0.5, 0.0, -1.0), coord(2.5, 2.0, 1.0));
+    let flame = DensityGrid::from_perlin(32, 3.0);
+    let fire = GridMedium::new(flame.clone(), fire_box, 3.0, Color::new(0.3, 0.3, 0.3))
+        .with_emission(flame, Color::new(4.0, 1.5, 0.3));
+    world.add(fire.boxed());
+
+    // milk and skin, by the walk inside and by the mean free paths
+    let milk = Subsurface::new_with_phase(
+        Sphere::new(coord(-1.2, 0.4, 2.0), 0.4, white.clone_box()).boxed(),
+        Color::new(0.95, 0.95, 0.9),
+        Color::new(0.3, 0.3, 0.3),
+        1.35,
+        Box::new(HenyeyGreenstein::new(0.7)),
+    );
+    world.add(milk.boxed());
+    let skin = Subsurface::new(
+        Sphere::new(coord(0.0, 0.4, 2.0), 0.4, white.clone_box()).boxed(),
+        Color::new(0.9, 0.7, 0.6),
+        Color::new(0.4, 0.15, 0.08),
+        1.4,
+    );
+    world.add(skin.boxed());
+    // haze in a glass
+    let glass = Sphere::new(coord(1.2, 0.4, 2.0), 0.4, white.clone_box());
+    world.add(ConstantMedium::new(glass.boxed(), 1.5, Color::new(0.6, 0.7, 0.9)).boxed());
+
+    let lamp = DiffuseLight::new_illuminant(Illuminant::F11, 8.0);
+    let lamp = Quad::new(
+        coord(-1.0, 3.5, 0.0),
+        coord(2.0, 0.0, 0.0),
+        coord(0.0, 0.0, 2.0),
+        Box::new(lamp),
+    );
+    world.add(lamp.boxed());
+
+    let mut c = camera::Camera::new(16.0 / 9.0, 600);
+    c.samples_per_pixel = 64;
+    c.max_deep_ray = 64;
+    c.lookfrom = coord(0.0, 1.5, 7.0);
+    c.lookat = coord(0.0, 0.8, 0.0);
+    c.vup = coord(0.0, 1.0, 0.0);
+    c.vfov = 45.0;
+    c.mode = RenderMode::Spectral;
+    c.fog = Some(Fog::new(0.01, Color::new(0.9, 0.9, 0.9)));
+    save(&c.render(&world), "volumes.png");
+    Ok(())
+}
+
+// balls of each glass on a white floor under a small light, the caustics
+// by the photons and then by the bidirectional
+fn caustics() {
+    let mut world = HittableList::new();
+    let white = Lambertian::new(Color::new(0.8, 0.8, 0.8));
+    world.add(Sphere::new(coord(0.0, -1000.0, 0.0), 1000.0, white.clone_box()).boxed());
+    let glasses = [
+        Dispersion::bk7(),
+        Dispersion::sf11(),
+        Dispersion::fused_silica(),
+        Dispersion::diamond(),
+        // the bk7 by Cauchy, only two terms
+        Dispersion::Cauchy {
+            a: 1.5046,
+            b: 0.0042,
+        },
+    ];
+    for (i, glass) in glasses.into_iter().enumerate() {
+        let m = DispersiveDieletric::new(glass).with_absorption(Color::new(0.05, 0.02, 0.0));
+        let s = Sphere::new(coord(i as f64 * 1.2 - 2.4, 0.5, 0.0), 0.5, Box::new(m));
+        world.add(s.boxed());
+    }
+
+    let mut c = camera::Camera::new(16.0 / 9.0, 600);
+    c.samples_per_pixel = 64;
+    c.lookfrom = coord(0.0, 2.5, 5.0);
+    c.lookat = coord(0.0, 0.3, 0.0);
+    c.vup = coord(0.0, 1.0, 0.0);
+    c.vfov = 45.0;
+    c.lights = LightList::new();
+    c.lights.add_sphere(
+        &mut world,
+        coord(0.0, 4.0, -2.0),
+        0.2,
+        Color::new(300.0, 300.0, 300.0),
+    );
+    c.photons = Some(PhotonSettings::new(200_000, 0.05).with_passes(16));
+    save(&c.render(&world), "caustics_photons.png");
+    c.photons = None;
+    c.integrator = Integrator::Bidirectional;
+    save(&c.render(&world), "caustics_bidirectional.png");
+}
+
+// a long render in passes, saved in checkpoint=(render.ckpt) to continue
+// after a kill, until the noise or the time, then the glass get 4x the
+// samples in a crop pasted over it
+fn progressive(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut world = HittableList::new();
+    let white = Lambertian::new(Color::new(0.7, 0.7, 0.7));
+    world.add(Sphere::new(coord(0.0, -1000.0, 0.0), 1000.0, white.clone_box()).boxed());
+    world.add(Sphere::new(coord(0.0, 0.5, 0.0), 0.5, Box::new(Dieletric::new(1.5))).boxed());
+    let red = Lambertian::new(Color::new(0.7, 0.1, 0.1));
+    world.add(Sphere::new(coord(-1.2, 0.5, -0.5), 0.5, red.clone_box()).boxed());
+
+    let mut c = camera::Camera::new(16.0 / 9.0, 800);
+    c.lookfrom = coord(0.0, 1.5, 4.0);
+    c.lookat = coord(0.0, 0.5, 0.0);
+    c.vup = coord(0.0, 1.0, 0.0);
+    c.vfov = 40.0;
+    c.tile_size = 16;
+    c.tile_order = TileOrder::Hilbert;
+    c.lights = LightList::new();
+    c.lights.add_sphere(
+        &mut world,
+        coord(2.0, 3.0, 1.0),
+        0.3,
+        Color::new(30.0, 30.0, 30.0),
+    );
+
+    let checkpoint = Checkpoint::new(arg(args, "checkpoint").unwrap_or("render.ckpt"))
+        .with_interval(Duration::from_secs(30));
+    let stop = Progressive::new(256)
+        .with_time(Duration::from_secs(600))
+        .with_noise(0.005);
+    let mut image = c.render_checkpointed(&world, stop, &checkpoint, |p, film| {
+        println!(
+            "{}x{} {} samples, {:?}, noise {:.4}",
+            film.width(),
+            film.height(),
+            p.samples,
+            p.elapsed,
+            p.noise
+        );
+        // a look in the render so far
+        if p.samples % 16 == 0 {
+            save(&film.image(), "preview.png");
+        }
+        true
+    })?;
+
+    // the glass is in the middle of the image
+    let glass = Region::new(300, 120, 200, 200);
+    c.crop = Some(glass);
+    c.tile_order = TileOrder::Spiral;
+    let detail = c.render_progressive(&world, Progressive::new(1024), |_, _| true);
+    glass.paste(&detail, &mut image);
+    save(&image, "progressive.png");
+    Ok(())
+}
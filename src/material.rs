@@ -1,8 +1,16 @@
+use std::f64::consts::PI;
+
 use crate::{
     color::Color,
+    microfacet::{TrowbridgeReitz, fresnel_conductor, fresnel_dielectric, reflect, refract},
+    onb::Onb,
+    phase::{Isotropic, PhaseFunction},
     ray::{HitRecord, Ray},
-    utils::randon_f64,
-    vec::Vec3,
+    spectrum::{self, Illuminant, SampledSpectrum, SampledWavelengths},
+    texture::{self, TextureRef},
+    thinfilm::ThinFilm,
+    utils::{degrees_to_radians, randon_f64},
+    vec::{Vec3, VecTypes},
 };
 
 pub trait Material: Sync {
@@ -17,6 +25,70 @@ pub trait Material: Sync {
 
     // used for "clone trait" in HitRecord
     fn clone_box(&self) -> Box<dyn Material>;
+
+    // light emitted by the material, most of then dont emit
+    #[allow(unused)]
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        Color::default()
+    }
+
+    // emitted light in the wavelengths of a spectral path
+    // by default the rgb is converted to a spectrum with D65 as white
+    fn emitted_spectral(&self, rec: &HitRecord, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum::from_emission(self.emitted(rec).rgb_array(), lambdas)
+    }
+
+    // the material split the wavelengths, so a spectral path can only
+    // follow the hero wavelength after hit it
+    fn is_dispersive(&self) -> bool {
+        false
+    }
+
+    // point inside a volume, without a surface and normal, the light
+    // arriving is not weighted by a cos
+    fn is_medium(&self) -> bool {
+        false
+    }
+
+    // scattering function in the direction wi (with the cos for surfaces)
+    // used by light sampling, that choose wi by itself
+    #[allow(unused)]
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        Color::default()
+    }
+
+    // pdf of reflect choose wi, 0.0 for perfect specular materials
+    // that can't be light sampled
+    #[allow(unused)]
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        0.0
+    }
+
+    // only reflect or refract in exact directions, like a mirror or a smooth
+    // glass, the eval and pdf are 0.0 and no light sampling reach it
+    #[allow(unused)]
+    fn is_delta(&self, rec: &HitRecord) -> bool {
+        false
+    }
+
+    // reflect, with the pdf of the direction chosen for the MIS of the light
+    // it find, 0.0 when chosen by a delta lobe, None when absorbed
+    // a material with delta and other lobes say which one it used
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> Option<f64> {
+        if !self.reflect(r_in, r_ref, rec, attenuation) {
+            return None;
+        }
+        if self.is_delta(rec) {
+            return Some(0.0);
+        }
+        Some(self.pdf(r_in, rec, &r_ref.direction))
+    }
 }
 
 // a DefaultMaterial for default impl in HitRecord
@@ -39,6 +111,73 @@ impl Material for DefaultMaterial {
     }
 }
 
+// a surface that emit light and dont reflect anything
+// the spectrum is a rgb color or a measured illuminant
+pub struct DiffuseLight {
+    emit: Color,
+    illuminant: Option<Illuminant>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self {
+            emit,
+            illuminant: None,
+        }
+    }
+
+    // light with the spectrum of the illuminant, with luminance scale
+    // in the rgb mode is used the rgb of this spectrum
+    pub fn new_illuminant(illuminant: Illuminant, scale: f64) -> Self {
+        let [r, g, b] = illuminant.to_rgb();
+        Self {
+            emit: Color::new(r, g, b).mul(scale),
+            illuminant: Some(illuminant),
+        }
+    }
+
+    fn scale(&self) -> f64 {
+        // luminance of the emit color
+        0.2126 * self.emit.rgb[0] + 0.7152 * self.emit.rgb[1] + 0.0722 * self.emit.rgb[2]
+    }
+}
+
+#[allow(unused)]
+impl Material for DiffuseLight {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            emit: self.emit,
+            illuminant: self.illuminant,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        false
+    }
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        // only the front side emit
+        if rec.front_face {
+            self.emit
+        } else {
+            Color::default()
+        }
+    }
+    fn emitted_spectral(&self, rec: &HitRecord, lambdas: &SampledWavelengths) -> SampledSpectrum {
+        match self.illuminant {
+            Some(ill) if rec.front_face => {
+                let scale = self.scale();
+                SampledSpectrum::from_fn(lambdas, |l| ill.value(l) * scale)
+            }
+            _ => SampledSpectrum::from_emission(self.emitted(rec).rgb_array(), lambdas),
+        }
+    }
+}
+
 // albedo -> latim of whiteness
 // lambertian will be a diffuse Material
 // with it albedo
@@ -75,14 +214,113 @@ impl Material for Lambertian {
             ref_dir = rec.normal;
         }
 
-        *r_ref = Ray::new(rec.point, ref_dir);
+        *r_ref = r_in.spawn(rec.point, ref_dir);
         *attenuation = self.albedo;
         true
     }
+    // the sample above is cosine weighted, so the pdf is cos / pi
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        let cos = rec.normal.dot(&wi.unit_vec());
+        if cos <= 0.0 {
+            return Color::default();
+        }
+        self.albedo.mul(cos / PI)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        rec.normal.dot(&wi.unit_vec()).max(0.0) / PI
+    }
+}
+
+// rough diffuse, the surface is made of V shaped lambertian microfacets
+// with sigma(in degrees) the deviation of their slopes, so it back scatter
+// more in grazing light, like clay, concrete or the moon
+// sigma 0.0 is the Lambertian, the sample is the same, only the weight change
+pub struct OrenNayar {
+    albedo: Color,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f64) -> Self {
+        let sigma = degrees_to_radians(sigma);
+        let sigma2 = sigma * sigma;
+        Self {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    // the Oren-Nayar term over the Lambertian, in the qualitative model
+    fn factor(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        let wo = r_in.direction.unit_vec().mul(-1.0);
+        let wi = wi.unit_vec();
+        let cos_o = rec.normal.dot(&wo).clamp(0.0, 1.0);
+        let cos_i = rec.normal.dot(&wi).clamp(0.0, 1.0);
+        let sin_o = (1.0 - cos_o * cos_o).sqrt();
+        let sin_i = (1.0 - cos_i * cos_i).sqrt();
+
+        // cos of the diference of the azimuths, by the projections in the tangent plane
+        let cos_phi = if sin_o > 1e-4 && sin_i > 1e-4 {
+            let po = wo - rec.normal.mul(cos_o);
+            let pi = wi - rec.normal.mul(cos_i);
+            (po.dot(&pi) / (sin_o * sin_i)).max(0.0)
+        } else {
+            0.0
+        };
+
+        // alpha is the bigger angle and beta the smaller one
+        let (sin_alpha, tan_beta) = if cos_i > cos_o {
+            (sin_o, sin_i / cos_i)
+        } else {
+            (sin_i, sin_o / cos_o.max(1e-4))
+        };
+        self.a + self.b * cos_phi * sin_alpha * tan_beta
+    }
+}
+
+impl Material for OrenNayar {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            albedo: self.albedo,
+            a: self.a,
+            b: self.b,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        // cosine weighted like the Lambertian, so the weight is albedo * factor
+        let mut ref_dir = rec.normal + Vec3::random_unit_vec();
+        if ref_dir.near_zero() {
+            ref_dir = rec.normal;
+        }
+
+        *r_ref = r_in.spawn(rec.point, ref_dir);
+        *attenuation = self.albedo.mul(self.factor(r_in, rec, &ref_dir));
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        let cos = rec.normal.dot(&wi.unit_vec());
+        if cos <= 0.0 {
+            return Color::default();
+        }
+        self.albedo.mul(self.factor(r_in, rec, wi) * cos / PI)
+    }
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        rec.normal.dot(&wi.unit_vec()).max(0.0) / PI
+    }
 }
 
 // TODO: put albedo color in trait and new funciton(see how place funciton in trait)
 
+// legacy metal of the book, fuzz is not energy conserving and can send
+// rays inside the surface, Conductor is the physically based one
 pub struct Metal {
     albedo: Color,
     // fuzz is [0.0, 1.0], 1.0 is total random, like matte metal, 0.0 is total reflected metal
@@ -113,20 +351,221 @@ impl Material for Metal {
         let mut reflect_dir = r_in.direction.reflected_vec(&rec.normal);
         // add fuzzy reflection, let the metal matte
         reflect_dir = reflect_dir.unit_vec() + Vec3::random_unit_vec().mul(self.fuzz);
-        *r_ref = Ray::new(rec.point, reflect_dir);
+        *r_ref = r_in.spawn(rec.point, reflect_dir);
         *attenuation = self.albedo;
         true
     }
+    fn is_delta(&self, _rec: &HitRecord) -> bool {
+        self.fuzz == 0.0
+    }
+}
+
+// physically based metal, GGX microfacets with Smith shadowing and the exact
+// Fresnel of a conductor, eta and k are the complex index by rgb channel
+// the roughness 0.0 is a perfect mirror
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distrib: TrowbridgeReitz,
+    film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Self {
+            eta,
+            k,
+            distrib: TrowbridgeReitz::from_roughness(roughness),
+            film: None,
+        }
+    }
+
+    // oxide or oil over the metal, like heated titanium or a beetle shell
+    pub fn with_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.143, 0.374, 1.442),
+            Color::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.200, 0.924, 1.102),
+            Color::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminum(roughness: f64) -> Self {
+        Self::new(
+            Color::new(1.657, 0.880, 0.521),
+            Color::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(
+            Color::new(0.155, 0.117, 0.138),
+            Color::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn fresnel(&self, cos: f64) -> Color {
+        let f = |c: usize| fresnel_conductor(cos, self.eta.rgb[c], self.k.rgb[c]);
+        Color::new(f(0), f(1), f(2))
+    }
+
+    // fresnel with the film, if there is one
+    fn fresnel_at(&self, cos: f64, r_in: &Ray, rec: &HitRecord) -> Color {
+        match &self.film {
+            Some(film) => film.fresnel(cos, rec, r_in.wavelength, 1.0, self.eta, self.k),
+            None => self.fresnel(cos),
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            eta: self.eta,
+            k: self.k,
+            distrib: self.distrib,
+            film: self.film.clone(),
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        if wo[2] <= 0.0 {
+            return false;
+        }
+
+        if self.distrib.effectively_smooth() {
+            let wi = Vec3::new(VecTypes::Coordinates, -wo[0], -wo[1], wo[2]);
+            *r_ref = r_in.spawn(rec.point, frame.transform(&wi));
+            *attenuation = self.fresnel_at(wo[2], r_in, rec);
+            return true;
+        }
+
+        // sample only the microfacets visible from wo, the reflected ray
+        // can still go below the surface, in that case the energy is lost
+        let wm = self.distrib.sample_wm(&wo);
+        let wi = reflect(&wo, &wm);
+        if wi[2] <= 0.0 {
+            return false;
+        }
+        *r_ref = r_in.spawn(rec.point, frame.transform(&wi));
+        // f * cos / pdf simplify to F * G / G1
+        *attenuation = self
+            .fresnel_at(wo.dot(&wm), r_in, rec)
+            .mul(self.distrib.g(&wo, &wi) / self.distrib.g1(&wo));
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        if self.distrib.effectively_smooth() {
+            return Color::default();
+        }
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        let wi = frame.inverse_transform(&wi.unit_vec());
+        if wo[2] <= 0.0 || wi[2] <= 0.0 {
+            return Color::default();
+        }
+        let wm = (wo + wi).unit_vec();
+        // D * F * G / (4 cos_o cos_i), times the cos_i
+        self.fresnel_at(wo.dot(&wm), r_in, rec)
+            .mul(self.distrib.d(&wm) * self.distrib.g(&wo, &wi) / (4.0 * wo[2]))
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        if self.distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        let wi = frame.inverse_transform(&wi.unit_vec());
+        if wo[2] <= 0.0 || wi[2] <= 0.0 {
+            return 0.0;
+        }
+        let wm = (wo + wi).unit_vec();
+        self.distrib.d_visible(&wo, &wm) / (4.0 * wo.dot(&wm).abs())
+    }
+    fn is_delta(&self, _rec: &HitRecord) -> bool {
+        self.distrib.effectively_smooth()
+    }
+    fn is_dispersive(&self) -> bool {
+        self.film.is_some()
+    }
 }
 
 pub struct Dieletric {
     // index relative to air/vacuum
     refraction_index: f64,
+    // absorption coefficient by unit of distance inside the object (Beer-Lambert)
+    // zero is a perfectly clear glass
+    sigma_a: Color,
+    film: Option<ThinFilm>,
 }
 
 impl Dieletric {
     pub fn new(refraction_index: f64) -> Self {
-        Self { refraction_index }
+        Self::new_with_coefficient(refraction_index, Color::default())
+    }
+
+    // colored glass, a ray that walk distance inside the object get the color
+    // more distance more dark and saturated, like wine in a glass
+    pub fn new_absorbing(refraction_index: f64, color: Color, distance: f64) -> Self {
+        // color = exp(-sigma * distance), so sigma = -ln(color) / distance
+        let sigma = |c: f64| -c.clamp(1e-6, 1.0).ln() / distance.max(1e-6);
+        Self::new_with_coefficient(
+            refraction_index,
+            Color::new(
+                sigma(color.rgb[0]),
+                sigma(color.rgb[1]),
+                sigma(color.rgb[2]),
+            ),
+        )
+    }
+
+    pub fn new_with_coefficient(refraction_index: f64, sigma_a: Color) -> Self {
+        Self {
+            refraction_index,
+            sigma_a,
+            film: None,
+        }
+    }
+
+    // iridescent coat over the glass, like a soap bubble
+    pub fn with_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+
+    // fraction of light that survive after walk inside the object from
+    // the r_in origin to the hit, only make sense when the hit is in the back face
+    pub fn transmittance(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            return Color::new(1.0, 1.0, 1.0);
+        }
+        let dist = rec.t * r_in.direction.vec_length();
+        Color::new(
+            (-self.sigma_a.rgb[0] * dist).exp(),
+            (-self.sigma_a.rgb[1] * dist).exp(),
+            (-self.sigma_a.rgb[2] * dist).exp(),
+        )
     }
 
     // Schlick aproach to refract based on a angle
@@ -140,6 +579,8 @@ impl Material for Dieletric {
     fn clone_box(&self) -> Box<dyn Material> {
         Box::new(Self {
             refraction_index: self.refraction_index,
+            sigma_a: self.sigma_a,
+            film: self.film.clone(),
         })
     }
     fn reflect(
@@ -149,7 +590,8 @@ impl Material for Dieletric {
         rec: &HitRecord,
         attenuation: &mut Color,
     ) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
+        // hitting the back face the ray was inside, so absorb by the walked distance
+        *attenuation = self.transmittance(r_in, rec);
         let ri = if rec.front_face {
             1.0 / self.refraction_index
         } else {
@@ -161,6 +603,38 @@ impl Material for Dieletric {
         let cos_theta = dir_unit.mul(-1.0).dot(&rec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
+        if let Some(film) = &self.film
+            && ri * sin_theta <= 1.0
+        {
+            // the film reflect diferent by channel, so choose by the mean
+            // and weight the choosed side by its probability
+            let (eta_i, eta_t) = if rec.front_face {
+                (1.0, self.refraction_index)
+            } else {
+                (self.refraction_index, 1.0)
+            };
+            let f = film.fresnel(
+                cos_theta,
+                rec,
+                r_in.wavelength,
+                eta_i,
+                Color::new(eta_t, eta_t, eta_t),
+                Color::default(),
+            );
+            let p = ((f.rgb[0] + f.rgb[1] + f.rgb[2]) / 3.0).clamp(1e-4, 1.0 - 1e-4);
+            let (dir_ref, weight) = if randon_f64() < p {
+                (dir_unit.reflected_vec(&rec.normal), f.mul(1.0 / p))
+            } else {
+                (
+                    dir_unit.refract(&rec.normal, ri),
+                    Color::new(1.0 - f.rgb[0], 1.0 - f.rgb[1], 1.0 - f.rgb[2]).mul(1.0 / (1.0 - p)),
+                )
+            };
+            *r_ref = r_in.spawn(rec.point, dir_ref);
+            *attenuation = *attenuation * weight;
+            return true;
+        }
+
         // break the lens law these edge cases, so reflect and not refract
         // || use the Schlick aproach
         let dir_ref = if ri * sin_theta > 1.0 || self.reflectance(cos_theta) > randon_f64() {
@@ -168,8 +642,379 @@ impl Material for Dieletric {
         } else {
             dir_unit.refract(&rec.normal, ri)
         };
-        *r_ref = Ray::new(rec.point, dir_ref);
+        *r_ref = r_in.spawn(rec.point, dir_ref);
         // a better name would be scattered
         true
     }
+    fn is_dispersive(&self) -> bool {
+        // the film reflectance is for the wavelength of the path
+        self.film.is_some()
+    }
+    fn is_delta(&self, _rec: &HitRecord) -> bool {
+        true
+    }
+}
+
+// frosted glass, GGX microfacets that reflect and refract, chosen by the
+// exact Fresnel of each microfacet, roughness 0.0 is the smooth Dieletric
+// like Dieletric the radiance is not scaled by eta^2 when refracting
+pub struct RoughDieletric {
+    refraction_index: f64,
+    distrib: TrowbridgeReitz,
+}
+
+impl RoughDieletric {
+    pub fn new(refraction_index: f64, roughness: f64) -> Self {
+        Self {
+            refraction_index,
+            distrib: TrowbridgeReitz::from_roughness(roughness),
+        }
+    }
+
+    // relative index, the normal of rec always point to the side of the ray
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            self.refraction_index
+        } else {
+            1.0 / self.refraction_index
+        }
+    }
+}
+
+impl Material for RoughDieletric {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            refraction_index: self.refraction_index,
+            distrib: self.distrib,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        let eta = self.eta(rec);
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        if wo[2] <= 0.0 {
+            return false;
+        }
+        *attenuation = Color::new(1.0, 1.0, 1.0);
+
+        if self.distrib.effectively_smooth() {
+            // same of Dieletric, but with the exact Fresnel
+            let n = Vec3::new(VecTypes::Coordinates, 0.0, 0.0, 1.0);
+            let wi = match refract(&wo, &n, eta) {
+                Some(t) if randon_f64() >= fresnel_dielectric(wo[2], eta) => t,
+                _ => reflect(&wo, &n),
+            };
+            *r_ref = r_in.spawn(rec.point, frame.transform(&wi));
+            return true;
+        }
+
+        // choose reflection or transmission by the Fresnel of the visible microfacet
+        let wm = self.distrib.sample_wm(&wo);
+        let wi = match refract(&wo, &wm, eta) {
+            Some(t) if randon_f64() >= fresnel_dielectric(wo.dot(&wm), eta) => {
+                // the microfacet can send it back to the side of wo
+                if t[2] >= 0.0 {
+                    return false;
+                }
+                t
+            }
+            // total internal reflection always reflect
+            _ => {
+                let r = reflect(&wo, &wm);
+                if r[2] <= 0.0 {
+                    return false;
+                }
+                r
+            }
+        };
+        let (f, pdf) = self.distrib.dielectric(&wo, &wi, eta);
+        if pdf <= 0.0 {
+            return false;
+        }
+        *r_ref = r_in.spawn(rec.point, frame.transform(&wi));
+        *attenuation = Color::new(1.0, 1.0, 1.0).mul(f / pdf);
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        if self.distrib.effectively_smooth() {
+            return Color::default();
+        }
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        let wi = frame.inverse_transform(&wi.unit_vec());
+        let (f, _) = self.distrib.dielectric(&wo, &wi, self.eta(rec));
+        Color::new(f, f, f)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        if self.distrib.effectively_smooth() {
+            return 0.0;
+        }
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        let wi = frame.inverse_transform(&wi.unit_vec());
+        self.distrib.dielectric(&wo, &wi, self.eta(rec)).1
+    }
+    fn is_delta(&self, _rec: &HitRecord) -> bool {
+        self.distrib.effectively_smooth()
+    }
+}
+
+// how the refraction index change with the wavelength(in micrometers)
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    // n = a + b / lambda^2
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum b_i lambda^2 / (lambda^2 - c_i)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn ior(&self, lambda_nm: f64) -> f64 {
+        let l2 = (lambda_nm / 1000.0).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+
+    // common crown glass, n ~ 1.517
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    // dense flint glass, n ~ 1.785 and a lot of dispersion, good for prisms
+    pub fn sf11() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.73759695, 0.313747346, 1.89878101],
+            c: [0.013188707, 0.0623068142, 155.23629],
+        }
+    }
+
+    // n ~ 1.458
+    pub fn fused_silica() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [
+                0.0684043f64.powi(2),
+                0.1162414f64.powi(2),
+                9.896161f64.powi(2),
+            ],
+        }
+    }
+
+    // n ~ 2.417, the fire of the diamond
+    pub fn diamond() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.175f64.powi(2), 0.106f64.powi(2), 0.0],
+        }
+    }
+}
+
+// Dieletric where the refraction index depend on the wavelength, so each color
+// refract to a diferent direction and we get rainbows in prisms
+// the path need a single wavelength, choosed in the first hit if the ray dont have one
+pub struct DispersiveDieletric {
+    dispersion: Dispersion,
+    sigma_a: Color,
+}
+
+impl DispersiveDieletric {
+    pub fn new(dispersion: Dispersion) -> Self {
+        Self {
+            dispersion,
+            sigma_a: Color::default(),
+        }
+    }
+
+    pub fn with_absorption(mut self, sigma_a: Color) -> Self {
+        self.sigma_a = sigma_a;
+        self
+    }
+}
+
+impl Material for DispersiveDieletric {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            dispersion: self.dispersion,
+            sigma_a: self.sigma_a,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        // from here the path only carry one wavelength, with its color as weight
+        let (lambda, weight) = match r_in.wavelength {
+            Some(l) => (l, Color::new(1.0, 1.0, 1.0)),
+            None => {
+                let l = spectrum::sample_wavelength();
+                (l, Color::from_wavelength(l))
+            }
+        };
+        let r_lambda = Ray {
+            wavelength: Some(lambda),
+            ..*r_in
+        };
+
+        // same boundary of Dieletric, with the index of this wavelength
+        let d = Dieletric::new_with_coefficient(self.dispersion.ior(lambda), self.sigma_a);
+        let hit = d.reflect(&r_lambda, r_ref, rec, attenuation);
+        *attenuation = *attenuation * weight;
+        hit
+    }
+
+    fn is_dispersive(&self) -> bool {
+        true
+    }
+
+    fn is_delta(&self, _rec: &HitRecord) -> bool {
+        true
+    }
+}
+
+// choose between two materials in each hit, by a weight or a mask texture
+// the mask 0.0 is all the first material and 1.0 all the second
+// good for rust patches over metal or dirt over paint
+pub struct MixMaterial {
+    a: Box<dyn Material>,
+    b: Box<dyn Material>,
+    mask: TextureRef,
+}
+
+impl MixMaterial {
+    pub fn new(a: Box<dyn Material>, b: Box<dyn Material>, weight: f64) -> Self {
+        Self::with_mask(a, b, texture::constant(weight))
+    }
+
+    pub fn with_mask(a: Box<dyn Material>, b: Box<dyn Material>, mask: TextureRef) -> Self {
+        Self { a, b, mask }
+    }
+
+    fn weight(&self, rec: &HitRecord) -> f64 {
+        self.mask.scalar(rec.u, rec.v, &rec.point).clamp(0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            a: self.a.clone_box(),
+            b: self.b.clone_box(),
+            mask: self.mask.clone(),
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        self.scatter(r_in, r_ref, rec, attenuation).is_some()
+    }
+    // the delta lobe of a mirror or glass is not light sampled, so the light
+    // found by it keep all its weight, the other lobe is weighted by the pdf
+    // of the mix, the delta one add nothing to it
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> Option<f64> {
+        let chosen = if randon_f64() < self.weight(rec) {
+            &self.b
+        } else {
+            &self.a
+        };
+        let pdf = chosen.scatter(r_in, r_ref, rec, attenuation)?;
+        if pdf == 0.0 {
+            return Some(0.0);
+        }
+        Some(self.pdf(r_in, rec, &r_ref.direction))
+    }
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        let w = self.weight(rec);
+        self.a.emitted(rec).mul(1.0 - w) + self.b.emitted(rec).mul(w)
+    }
+    fn is_dispersive(&self) -> bool {
+        self.a.is_dispersive() || self.b.is_dispersive()
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        let w = self.weight(rec);
+        self.a.eval(r_in, rec, wi).mul(1.0 - w) + self.b.eval(r_in, rec, wi).mul(w)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        let w = self.weight(rec);
+        self.a.pdf(r_in, rec, wi) * (1.0 - w) + self.b.pdf(r_in, rec, wi) * w
+    }
+    fn is_delta(&self, rec: &HitRecord) -> bool {
+        let w = self.weight(rec);
+        (w == 1.0 || self.a.is_delta(rec)) && (w == 0.0 || self.b.is_delta(rec))
+    }
+}
+
+// material of a point inside a volume, the light is scattered by the
+// phase function and the albedo is the fraction that is not absorbed
+pub struct Volumetric {
+    albedo: Color,
+    phase: Box<dyn PhaseFunction>,
+}
+
+impl Volumetric {
+    pub fn new(albedo: Color, phase: Box<dyn PhaseFunction>) -> Self {
+        Self { albedo, phase }
+    }
+
+    pub fn isotropic(albedo: Color) -> Self {
+        Self::new(albedo, Box::new(Isotropic {}))
+    }
+}
+
+impl Material for Volumetric {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            albedo: self.albedo,
+            phase: self.phase.clone_box(),
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        let (wi, _) = self.phase.sample(&r_in.direction.unit_vec());
+        *r_ref = r_in.spawn(rec.point, wi);
+        // the sample follow the p exactly, so p / pdf is 1.0
+        *attenuation = self.albedo;
+        true
+    }
+    fn eval(&self, r_in: &Ray, _rec: &HitRecord, wi: &Vec3) -> Color {
+        self.albedo
+            .mul(self.phase.p(&r_in.direction.unit_vec(), &wi.unit_vec()))
+    }
+    fn pdf(&self, r_in: &Ray, _rec: &HitRecord, wi: &Vec3) -> f64 {
+        self.phase.pdf(&r_in.direction.unit_vec(), &wi.unit_vec())
+    }
+    fn is_medium(&self) -> bool {
+        true
+    }
 }
@@ -0,0 +1,123 @@
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    material::{Material, Volumetric},
+    phase::{Isotropic, PhaseFunction},
+    ray::{HitRecord, Hittable, Ray},
+    utils::{INF, randon_f64},
+    vec::{Vec3, VecTypes},
+};
+
+// sample the distance a ray walk inside a medium before scatter
+// the probability decay exponentially with the distance (Beer-Lambert)
+pub fn sample_distance(density: f64) -> f64 {
+    -(1.0 - randon_f64()).ln() / density
+}
+
+// fill the boundary with a participating media, like smoke or fog
+// the boundary need to be a closed shape
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable + Sync>,
+    density: f64,
+    phase_function: Volumetric,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hittable + Sync>, density: f64, albedo: Color) -> Self {
+        Self::new_with_phase(boundary, density, albedo, Box::new(Isotropic {}))
+    }
+
+    pub fn new_with_phase(
+        boundary: Box<dyn Hittable + Sync>,
+        density: f64,
+        albedo: Color,
+        phase: Box<dyn PhaseFunction>,
+    ) -> Self {
+        ConstantMedium {
+            boundary,
+            density,
+            phase_function: Volumetric::new(albedo, phase),
+        }
+    }
+
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
+        let mut rec1 = HitRecord::default();
+        let mut rec2 = HitRecord::default();
+
+        // find the entry and exit of the boundary in all the line, so works
+        // when the ray starts inside the volume, in this case the entry is behind
+        if !self.boundary.hit(r, INF, -INF, &mut rec1) {
+            return false;
+        }
+        if !self.boundary.hit(r, INF, rec1.t + 0.0001, &mut rec2) {
+            return false;
+        }
+
+        // clip the segment inside the volume to [r_min, r_max]
+        let t_enter = rec1.t.max(r_min);
+        let t_exit = rec2.t.min(r_max);
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        let ray_len = r.direction.vec_length();
+        let dist_inside = (t_exit - t_enter) * ray_len;
+        let hit_dist = sample_distance(self.density);
+        if hit_dist > dist_inside {
+            return false;
+        }
+
+        rec.t = t_enter + hit_dist / ray_len;
+        rec.point = r.at(rec.t);
+        // normal and face are arbitrary in a volume
+        rec.normal = Vec3::new(VecTypes::Coordinates, 1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.mat = self.phase_function.clone_box();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+// global fog, a constant medium that fill the box of the whole world
+// objects far from camera fade into the fog color
+#[derive(Clone, Copy)]
+pub struct Fog {
+    pub density: f64,
+    pub albedo: Color,
+}
+
+impl Fog {
+    pub fn new(density: f64, albedo: Color) -> Self {
+        Fog { density, albedo }
+    }
+
+    // check if the ray scatter in the fog before the t_hit(INF if hit nothing)
+    // the fog only exists inside world_box, so rays leaving the world see the background
+    pub fn hit(&self, r: &Ray, world_box: &Aabb, t_hit: f64, rec: &mut HitRecord) -> bool {
+        let Some(inside) = world_box.hit_interval(r, t_hit, 0.001) else {
+            return false;
+        };
+
+        let ray_len = r.direction.vec_length();
+        let hit_dist = sample_distance(self.density);
+        if hit_dist > inside.size() * ray_len {
+            return false;
+        }
+
+        rec.t = inside.min + hit_dist / ray_len;
+        rec.point = r.at(rec.t);
+        rec.normal = Vec3::new(VecTypes::Coordinates, 1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.mat = Box::new(Volumetric::isotropic(self.albedo));
+        true
+    }
+}
@@ -0,0 +1,212 @@
+use std::f64::consts::PI;
+
+use crate::{
+    utils::randon_f64,
+    vec::{Vec3, VecTypes},
+};
+
+// all the functions here are in the local shading frame,
+// where the normal is (0, 0, 1) and the directions are unit and point away
+// from the surface, like in pbrt
+
+fn cos2_theta(w: &Vec3) -> f64 {
+    w[2] * w[2]
+}
+
+fn tan2_theta(w: &Vec3) -> f64 {
+    (1.0 - cos2_theta(w)).max(0.0) / cos2_theta(w)
+}
+
+// GGX / Trowbridge-Reitz distribution of the microfacets normals
+// alpha is the width of the distribution, near 0.0 is a mirror
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    pub alpha: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha: f64) -> Self {
+        Self { alpha }
+    }
+
+    // the roughness of the artists is more perceptually linear
+    pub fn from_roughness(roughness: f64) -> Self {
+        let r = roughness.clamp(0.0, 1.0);
+        Self::new(r * r)
+    }
+
+    // too smooth to sample, the material should be a perfect specular
+    pub fn effectively_smooth(&self) -> bool {
+        self.alpha < 1e-3
+    }
+
+    // density of microfacets with normal wm
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        let tan2 = tan2_theta(wm);
+        if tan2.is_infinite() {
+            return 0.0;
+        }
+        let cos4 = cos2_theta(wm).powi(2);
+        let e = 1.0 + tan2 / (self.alpha * self.alpha);
+        1.0 / (PI * self.alpha * self.alpha * cos4 * e * e)
+    }
+
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let tan2 = tan2_theta(w);
+        if tan2.is_infinite() {
+            return 0.0;
+        }
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    // Smith masking, fraction of the microfacets visible from w
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Smith masking-shadowing, for the pair of directions
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // distribution of the normals visible from w
+    pub fn d_visible(&self, w: &Vec3, wm: &Vec3) -> f64 {
+        self.g1(w) / w[2].abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    // sample a microfacet normal visible from w (Heitz 2018)
+    pub fn sample_wm(&self, w: &Vec3) -> Vec3 {
+        // stretch the view, so the distribution become a hemisphere
+        let wh = Vec3::new(
+            VecTypes::Coordinates,
+            self.alpha * w[0],
+            self.alpha * w[1],
+            w[2],
+        )
+        .unit_vec();
+        let wh = if wh[2] < 0.0 { wh.mul(-1.0) } else { wh };
+
+        let lensq = wh[0] * wh[0] + wh[1] * wh[1];
+        let t1 = if lensq > 0.0 {
+            Vec3::new(VecTypes::Coordinates, -wh[1], wh[0], 0.0).div(lensq.sqrt())
+        } else {
+            Vec3::new(VecTypes::Coordinates, 1.0, 0.0, 0.0)
+        };
+        let t2 = wh * t1;
+
+        // uniform point in the disk, warped to the visible part
+        let r = randon_f64().sqrt();
+        let phi = 2.0 * PI * randon_f64();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + wh[2]);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = t1.mul(p1) + t2.mul(p2) + wh.mul((1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt());
+        // back to the not stretched space
+        Vec3::new(
+            VecTypes::Coordinates,
+            self.alpha * nh[0],
+            self.alpha * nh[1],
+            nh[2].max(1e-6),
+        )
+        .unit_vec()
+    }
+
+    // rough dieletric interface, f * |cos_i| and the pdf of sample wi with
+    // the visible normals and the Fresnel choosing reflection or refraction
+    pub fn dielectric(&self, wo: &Vec3, wi: &Vec3, eta: f64) -> (f64, f64) {
+        let cos_o = wo[2];
+        let cos_i = wi[2];
+        if cos_o <= 0.0 || cos_i == 0.0 {
+            return (0.0, 0.0);
+        }
+        let reflection = cos_i > 0.0;
+
+        // generalized half vector, the microfacet that link wo and wi
+        let wm = if reflection {
+            *wo + *wi
+        } else {
+            wi.mul(eta) + *wo
+        };
+        if wm.near_zero() {
+            return (0.0, 0.0);
+        }
+        let wm = wm.unit_vec();
+        let wm = if wm[2] < 0.0 { wm.mul(-1.0) } else { wm };
+        // the microfacet can't be backfacing to any of the directions
+        if wm.dot(wi) * cos_i < 0.0 || wm.dot(wo) * cos_o < 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let r = fresnel_dielectric(wo.dot(&wm), eta);
+        let t = 1.0 - r;
+        let d = self.d(&wm);
+        let g = self.g(wo, wi);
+        let d_vis = self.d_visible(wo, &wm);
+
+        if reflection {
+            let f = d * g * r / (4.0 * cos_o);
+            let pdf = d_vis / (4.0 * wo.dot(&wm).abs()) * r;
+            (f, pdf)
+        } else {
+            let denom = (wi.dot(&wm) + wo.dot(&wm) / eta).powi(2);
+            let dwm_dwi = wi.dot(&wm).abs() / denom;
+            let f = t * d * g * (wi.dot(&wm) * wo.dot(&wm) / (cos_o * denom)).abs();
+            let pdf = d_vis * dwm_dwi * t;
+            (f, pdf)
+        }
+    }
+}
+
+// exact Fresnel reflectance of a conductor, with complex index eta + i k
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2plusb2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2plusb2 + cos2;
+    let a = (0.5 * (a2plusb2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2plusb2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+// reflect w by the normal n, both pointing away from the surface
+pub fn reflect(w: &Vec3, n: &Vec3) -> Vec3 {
+    w.mul(-1.0) + n.mul(2.0 * w.dot(n))
+}
+
+// exact Fresnel reflectance of a dieletric, eta is the index of the side of
+// transmission over the index of the side of cos_i, 1.0 in total internal reflection
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parl = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perp = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+// refract w through the normal n(same side of w), eta like in fresnel_dielectric
+// None in total internal reflection
+pub fn refract(w: &Vec3, n: &Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = w.dot(n);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(w.mul(-1.0 / eta) + n.mul(cos_i / eta - cos_t))
+}
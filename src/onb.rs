@@ -0,0 +1,41 @@
+use crate::vec::{Vec3, VecTypes};
+
+// orthonormal basis, used to sample directions relative to a normal
+// (or to a ray direction) and move then to world space
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    // w will be the n direction, u and v any perpendicular pair
+    pub fn new(n: &Vec3) -> Self {
+        let w = n.unit_vec();
+        // a vector that is not parallel to w
+        let a = if w[0].abs() > 0.9 {
+            Vec3::new(w.typ, 0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(w.typ, 1.0, 0.0, 0.0)
+        };
+        let v = (w * a).unit_vec();
+        let u = w * v;
+        Onb { u, v, w }
+    }
+
+    // local coordinates to world
+    pub fn transform(&self, a: &Vec3) -> Vec3 {
+        self.u.mul(a[0]) + self.v.mul(a[1]) + self.w.mul(a[2])
+    }
+
+    // world to local coordinates, (u, v, w) become (x, y, z)
+    pub fn inverse_transform(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(
+            VecTypes::Coordinates,
+            a.dot(&self.u),
+            a.dot(&self.v),
+            a.dot(&self.w),
+        )
+    }
+}
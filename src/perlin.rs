@@ -0,0 +1,100 @@
+use crate::vec::{Vec3, VecTypes};
+use rand::prelude::*;
+
+const POINT_COUNT: usize = 256;
+
+// Perlin noise, random unit vectors in a lattice with the hermitian smooth
+// of the book, it is deterministic after created
+pub struct Perlin {
+    randvec: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new() -> Self {
+        let randvec = (0..POINT_COUNT)
+            .map(|_| Vec3::random_max_min(-1.0, 1.0).unit_vec())
+            .collect();
+        Perlin {
+            randvec,
+            perm_x: Self::generate_perm(),
+            perm_y: Self::generate_perm(),
+            perm_z: Self::generate_perm(),
+        }
+    }
+
+    fn generate_perm() -> Vec<usize> {
+        let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+        p.shuffle(&mut rand::rng());
+        p
+    }
+
+    // value in [-1.0, 1.0]
+    pub fn noise(&self, p: &Vec3) -> f64 {
+        let u = p[0] - p[0].floor();
+        let v = p[1] - p[1].floor();
+        let w = p[2] - p[2].floor();
+
+        let i = p[0].floor() as i64;
+        let j = p[1].floor() as i64;
+        let k = p[2].floor() as i64;
+
+        let mut c = [[[Vec3::default(); 2]; 2]; 2];
+        for (di, ci) in c.iter_mut().enumerate() {
+            for (dj, cj) in ci.iter_mut().enumerate() {
+                for (dk, ck) in cj.iter_mut().enumerate() {
+                    *ck = self.randvec[self.perm_x[((i + di as i64) & 255) as usize]
+                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
+                        ^ self.perm_z[((k + dk as i64) & 255) as usize]];
+                }
+            }
+        }
+
+        Self::perlin_interp(&c, u, v, w)
+    }
+
+    // sum of noise with diferent frequencies
+    pub fn turb(&self, p: &Vec3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p = temp_p.mul(2.0);
+        }
+
+        accum.abs()
+    }
+
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        // hermitian cubic to round off the interpolation
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+        let mut accum = 0.0;
+
+        for (i, ci) in c.iter().enumerate() {
+            for (j, cj) in ci.iter().enumerate() {
+                for (k, ck) in cj.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::new(VecTypes::Coordinates, u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * ck.dot(&weight_v);
+                }
+            }
+        }
+        accum
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
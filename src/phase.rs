@@ -0,0 +1,140 @@
+use std::f64::consts::PI;
+
+use crate::{
+    onb::Onb,
+    utils::randon_f64,
+    vec::{Vec3, VecTypes},
+};
+
+// how a volume spread the light that it scatter
+// wo is the direction the light was going, wi the new direction, both unit vectors
+// the value is a density in the sphere of directions, so it integrate to 1.0
+pub trait PhaseFunction: Sync {
+    fn p(&self, wo: &Vec3, wi: &Vec3) -> f64;
+
+    // importance sample a new direction, return it with its pdf
+    fn sample(&self, wo: &Vec3) -> (Vec3, f64);
+
+    // pdf of sample choose wi, the sample of this phase functions follow
+    // exactly the p, so by default is the same
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        self.p(wo, wi)
+    }
+
+    fn clone_box(&self) -> Box<dyn PhaseFunction>;
+}
+
+// all directions with the same probability
+#[derive(Clone, Copy)]
+pub struct Isotropic {}
+
+impl PhaseFunction for Isotropic {
+    fn p(&self, _wo: &Vec3, _wi: &Vec3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn sample(&self, _wo: &Vec3) -> (Vec3, f64) {
+        (Vec3::random_unit_vec(), 1.0 / (4.0 * PI))
+    }
+
+    fn clone_box(&self) -> Box<dyn PhaseFunction> {
+        Box::new(*self)
+    }
+}
+
+// g in (-1.0, 1.0) is the mean cosine of the scatter
+// g > 0.0 scatter forward(clouds), g < 0.0 scatter backward(dust), 0.0 is isotropic
+#[derive(Clone, Copy)]
+pub struct HenyeyGreenstein {
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> Self {
+        Self {
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+
+    fn eval(g: f64, cos_theta: f64) -> f64 {
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.max(1e-12).sqrt())
+    }
+
+    // invert the cdf of the cos of the angle with wo
+    fn sample_g(g: f64, wo: &Vec3) -> Vec3 {
+        let xi = randon_f64();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let sqr = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            (1.0 + g * g - sqr * sqr) / (2.0 * g)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * randon_f64();
+        let local = Vec3::new(
+            VecTypes::Coordinates,
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        );
+        Onb::new(wo).transform(&local)
+    }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn p(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        Self::eval(self.g, wo.dot(wi))
+    }
+
+    fn sample(&self, wo: &Vec3) -> (Vec3, f64) {
+        let wi = Self::sample_g(self.g, wo);
+        (wi, self.p(wo, &wi))
+    }
+
+    fn clone_box(&self) -> Box<dyn PhaseFunction> {
+        Box::new(*self)
+    }
+}
+
+// mix of two HG lobes, w of the first and 1.0 - w of the second
+// a forward and a backward lobe give the look of clouds with a halo
+#[derive(Clone, Copy)]
+pub struct DoubleHenyeyGreenstein {
+    g1: f64,
+    g2: f64,
+    w: f64,
+}
+
+impl DoubleHenyeyGreenstein {
+    pub fn new(g1: f64, g2: f64, w: f64) -> Self {
+        Self {
+            g1: g1.clamp(-0.999, 0.999),
+            g2: g2.clamp(-0.999, 0.999),
+            w: w.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl PhaseFunction for DoubleHenyeyGreenstein {
+    fn p(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        let cos_theta = wo.dot(wi);
+        self.w * HenyeyGreenstein::eval(self.g1, cos_theta)
+            + (1.0 - self.w) * HenyeyGreenstein::eval(self.g2, cos_theta)
+    }
+
+    // choose the lobe by its weight, the pdf is the mix of the two
+    fn sample(&self, wo: &Vec3) -> (Vec3, f64) {
+        let g = if randon_f64() < self.w {
+            self.g1
+        } else {
+            self.g2
+        };
+        let wi = HenyeyGreenstein::sample_g(g, wo);
+        (wi, self.p(wo, &wi))
+    }
+
+    fn clone_box(&self) -> Box<dyn PhaseFunction> {
+        Box::new(*self)
+    }
+}
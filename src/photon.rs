@@ -0,0 +1,209 @@
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    ray::{HitRecord, HittableList, Ray},
+    utils,
+    vec::Vec3,
+};
+
+// caustics by photon mapping, photons leave the lights, cross the specular
+// materials and are stored where they land in a diffuse one, the camera
+// paths take the light of the photons near each hit
+// progressive: each pass shoot new photons with a smaller radius, so the
+// blur(bias) go away with the passes, alpha in (0.0, 1.0) is how fast
+#[derive(Debug, Clone, Copy)]
+pub struct PhotonSettings {
+    pub photons: usize,
+    pub passes: u32,
+    pub radius: f64,
+    pub alpha: f64,
+}
+
+impl PhotonSettings {
+    pub fn new(photons: usize, radius: f64) -> Self {
+        PhotonSettings {
+            photons,
+            passes: 1,
+            radius,
+            alpha: 0.7,
+        }
+    }
+
+    pub fn with_passes(mut self, passes: u32) -> Self {
+        self.passes = passes.max(1);
+        self
+    }
+
+    // radius of the pass(from 0), r² shrink by (i + alpha) / (i + 1)
+    pub fn radius_at(&self, pass: u32) -> f64 {
+        let mut r2 = self.radius * self.radius;
+        for i in 1..=pass {
+            r2 *= (i as f64 + self.alpha) / (i as f64 + 1.0);
+        }
+        r2.sqrt()
+    }
+}
+
+// light that arrived in a point, wi is the unit direction it came from
+#[derive(Clone, Copy)]
+pub struct Photon {
+    pub point: Vec3,
+    pub wi: Vec3,
+    pub power: Color,
+}
+
+// photons in a kd-tree, each range of the array has the median in the
+// middle, split in the widest axis, and the halves at the sides
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axis: Vec<u8>,
+    radius: f64,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>, radius: f64) -> Self {
+        let mut axis = vec![0; photons.len()];
+        Self::build(&mut photons, &mut axis);
+        PhotonMap {
+            photons,
+            axis,
+            radius,
+        }
+    }
+
+    // shoot the photons from the lights of the camera, the power of the map
+    // is the power of the lights, each photon has its random numbers from
+    // the seed, so the map is the same in any thread
+    pub fn trace(cam: &Camera, world: &HittableList, count: usize, radius: f64, seed: u64) -> Self {
+        let photons: Vec<Photon> = (0..count)
+            .into_par_iter()
+            .flat_map_iter(|i| {
+                utils::seed_rng(utils::hash_seed(&[seed, i as u64]));
+                trace_photon(cam, world, count)
+            })
+            .collect();
+        Self::new(photons, radius)
+    }
+
+    fn build(photons: &mut [Photon], axis: &mut [u8]) {
+        if photons.len() <= 1 {
+            return;
+        }
+        let (mut min, mut max) = (photons[0].point, photons[0].point);
+        for p in photons.iter() {
+            for a in 0..3 {
+                min[a] = min[a].min(p.point[a]);
+                max[a] = max[a].max(p.point[a]);
+            }
+        }
+        let size = max - min;
+        let a = (0..3)
+            .max_by(|i, j| size[*i].total_cmp(&size[*j]))
+            .unwrap_or(0);
+        let mid = photons.len() / 2;
+        photons.select_nth_unstable_by(mid, |p, q| p.point[a].total_cmp(&q.point[a]));
+        axis[mid] = a as u8;
+        let (left, right) = photons.split_at_mut(mid);
+        let (axis_left, axis_right) = axis.split_at_mut(mid);
+        Self::build(left, axis_left);
+        Self::build(&mut right[1..], &mut axis_right[1..]);
+    }
+
+    // all the photons at distance r of p
+    pub fn for_each_near<F: FnMut(&Photon)>(&self, p: &Vec3, r: f64, mut f: F) {
+        self.search(0, self.photons.len(), p, r * r, &mut f);
+    }
+
+    fn search<F: FnMut(&Photon)>(&self, lo: usize, hi: usize, p: &Vec3, r2: f64, f: &mut F) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let photon = &self.photons[mid];
+        if (photon.point - *p).vec_length().powi(2) <= r2 {
+            f(photon);
+        }
+        if hi - lo == 1 {
+            return;
+        }
+        let a = self.axis[mid] as usize;
+        let d = p[a] - photon.point[a];
+        // the side of p first, the other only if the sphere cross the plane
+        let (near, far) = if d <= 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.search(near.0, near.1, p, r2, f);
+        if d * d <= r2 {
+            self.search(far.0, far.1, p, r2, f);
+        }
+    }
+
+    // radiance going out of the hit to the camera by the photons near it
+    // density estimation in a disk of the radius of the map
+    pub fn estimate(&self, r_in: &Ray, h: &HitRecord) -> Color {
+        let mut accum = Color::default();
+        self.for_each_near(&h.point, self.radius, |photon| {
+            let cos = h.normal.dot(&photon.wi).abs();
+            if cos > 0.0 {
+                // eval has the cos, the photon power is already the flux
+                accum += h.mat.eval(r_in, h, &photon.wi).mul(1.0 / cos) * photon.power;
+            }
+        });
+        accum.mul(1.0 / (PI * self.radius * self.radius))
+    }
+}
+
+// one photon, stored where it land in a surface that is not delta after
+// crossing only delta lobes, it go on while the lobe it choose is delta, like
+// the glass half of a mix, so a point can have photons of more than one hit
+// the volumes and the paths without specular are left to the path tracer
+fn trace_photon(cam: &Camera, world: &HittableList, count: usize) -> Vec<Photon> {
+    let mut stored = Vec::new();
+    let Some((_, light, prob)) = cam.lights.choose_emitter() else {
+        return stored;
+    };
+    let Some(e) = light.sample_le() else {
+        return stored;
+    };
+    if e.pdf_pos == 0.0 || e.pdf_dir == 0.0 {
+        return stored;
+    }
+    let cos = if e.normal.near_zero() {
+        1.0
+    } else {
+        e.normal.dot(&e.dir).abs()
+    };
+    let mut power =
+        e.le.mul(cos / (prob * e.pdf_pos * e.pdf_dir * count as f64));
+    let mut r = Ray::new(e.origin, e.dir);
+    let mut specular = false;
+    for _ in 0..cam.max_deep_ray {
+        let mut h = HitRecord::default();
+        if !cam.hit_world(&r, world, &mut h) || h.mat.is_medium() {
+            break;
+        }
+        if specular && !h.mat.is_delta(&h) {
+            stored.push(Photon {
+                point: h.point,
+                wi: r.direction.unit_vec().mul(-1.0),
+                power,
+            });
+        }
+        let mut next = Ray::default();
+        let mut attenuation = Color::default();
+        // a lobe with pdf is the end, the path tracer take it from there
+        if h.mat.scatter(&r, &mut next, &h, &mut attenuation) != Some(0.0) {
+            break;
+        }
+        specular = true;
+        power = power * attenuation;
+        r = next;
+    }
+    stored
+}
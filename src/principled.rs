@@ -0,0 +1,361 @@
+use std::f64::consts::PI;
+
+use crate::{
+    color::Color,
+    material::Material,
+    microfacet::{TrowbridgeReitz, fresnel_dielectric, reflect, refract},
+    onb::Onb,
+    ray::{HitRecord, Ray},
+    texture::{TextureRef, constant, solid},
+    utils::randon_f64,
+    vec::{Vec3, VecTypes},
+};
+
+// smaller roughness would be a perfect specular, that this material dont sample
+const MIN_ROUGHNESS: f64 = 0.05;
+
+// the highlights of the dieletrics reflect little but are small and bright,
+// the specular is always chosen at least by this weight
+const MIN_SPECULAR_WEIGHT: f64 = 0.1;
+
+fn luminance(c: &Color) -> f64 {
+    0.2126 * c.rgb[0] + 0.7152 * c.rgb[1] + 0.0722 * c.rgb[2]
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    a.mul(1.0 - t) + b.mul(t)
+}
+
+// (1 - cos)^5 of the Schlick aproach
+fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos).clamp(0.0, 1.0).powi(5)
+}
+
+fn schlick(f0: Color, cos: f64) -> Color {
+    lerp(f0, Color::new(1.0, 1.0, 1.0), schlick_weight(cos))
+}
+
+fn random_cosine_direction() -> Vec3 {
+    let r1 = randon_f64();
+    let r2 = randon_f64();
+    let phi = 2.0 * PI * r1;
+    Vec3::new(
+        VecTypes::Coordinates,
+        phi.cos() * r2.sqrt(),
+        phi.sin() * r2.sqrt(),
+        (1.0 - r2).sqrt(),
+    )
+}
+
+// Disney style uber material, one shader for the assets from the DCC tools
+// all the parameters are textures, the numbers are in [0, 1] but the ior
+// the lobes are a diffuse with sheen, a GGX specular, a GGX clearcoat and a
+// rough transmission, sampled by their weights
+pub struct Principled {
+    pub base_color: TextureRef,
+    pub metallic: TextureRef,
+    pub roughness: TextureRef,
+    pub specular: TextureRef,
+    pub specular_tint: TextureRef,
+    pub sheen: TextureRef,
+    pub clearcoat: TextureRef,
+    pub clearcoat_gloss: TextureRef,
+    pub transmission: TextureRef,
+    pub ior: TextureRef,
+    pub emission: TextureRef,
+}
+
+// the parameters in the point of the hit
+struct Params {
+    base: Color,
+    metallic: f64,
+    roughness: f64,
+    specular: f64,
+    specular_tint: f64,
+    sheen: f64,
+    clearcoat: f64,
+    clearcoat_gloss: f64,
+    transmission: f64,
+    eta: f64,
+}
+
+impl Params {
+    // base color without its luminance, used by the tints
+    fn tint(&self) -> Color {
+        let lum = luminance(&self.base);
+        if lum > 0.0 {
+            self.base.mul(1.0 / lum)
+        } else {
+            Color::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    fn specular_f0(&self) -> Color {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let dieletric = lerp(white, self.tint(), self.specular_tint).mul(self.specular * 0.08);
+        lerp(dieletric, self.base, self.metallic)
+    }
+
+    fn distrib(&self) -> TrowbridgeReitz {
+        TrowbridgeReitz::from_roughness(self.roughness.max(MIN_ROUGHNESS))
+    }
+
+    fn clearcoat_distrib(&self) -> TrowbridgeReitz {
+        let alpha = 0.1 * (1.0 - self.clearcoat_gloss) + 0.001 * self.clearcoat_gloss;
+        TrowbridgeReitz::new(alpha.max(MIN_ROUGHNESS * MIN_ROUGHNESS))
+    }
+
+    // fraction of the light that enter the surface, for the diffuse and the
+    // transmission, the rest is reflected by the specular
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+
+    fn transmission_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+
+    // diffuse, specular, clearcoat, transmission, for wo with the cos
+    // the part of each lobe in the light reflected, the specular by its
+    // Fresnel and the diffuse by the rest, to sample them
+    fn lobe_weights(&self, cos_o: f64) -> [f64; 4] {
+        let fresnel = luminance(&schlick(self.specular_f0(), cos_o));
+        [
+            self.diffuse_weight() * (1.0 - fresnel),
+            fresnel.max(MIN_SPECULAR_WEIGHT),
+            0.25 * self.clearcoat,
+            self.transmission_weight(),
+        ]
+    }
+
+    fn lobe_probs(&self, cos_o: f64) -> [f64; 4] {
+        let w = self.lobe_weights(cos_o);
+        let sum: f64 = w.iter().sum();
+        w.map(|x| x / sum)
+    }
+}
+
+impl Principled {
+    pub fn new(base_color: Color) -> Self {
+        Self {
+            base_color: solid(base_color),
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            clearcoat_gloss: constant(1.0),
+            transmission: constant(0.0),
+            ior: constant(1.5),
+            emission: solid(Color::default()),
+        }
+    }
+
+    fn params(&self, rec: &HitRecord) -> Params {
+        let (u, v, p) = (rec.u, rec.v, &rec.point);
+        let s = |t: &TextureRef| t.scalar(u, v, p).clamp(0.0, 1.0);
+        let ior = self.ior.scalar(u, v, p).max(1.0 + 1e-4);
+        Params {
+            base: self.base_color.value(u, v, p),
+            metallic: s(&self.metallic),
+            roughness: s(&self.roughness),
+            specular: s(&self.specular),
+            specular_tint: s(&self.specular_tint),
+            sheen: s(&self.sheen),
+            clearcoat: s(&self.clearcoat),
+            clearcoat_gloss: s(&self.clearcoat_gloss),
+            transmission: s(&self.transmission),
+            eta: if rec.front_face { ior } else { 1.0 / ior },
+        }
+    }
+
+    // f * |cos_i| of all the lobes, in the local frame
+    fn eval_local(p: &Params, wo: &Vec3, wi: &Vec3) -> Color {
+        let cos_o = wo[2];
+        let cos_i = wi[2];
+        if cos_o <= 0.0 || cos_i == 0.0 {
+            return Color::default();
+        }
+        let w_trans = p.transmission_weight();
+
+        if cos_i < 0.0 {
+            // only the transmission go to the other side, the diffuse, sheen,
+            // specular and clearcoat reflect
+            return match Self::transmission_half(wo, wi, p.eta) {
+                Some(wm) => {
+                    let distrib = p.distrib();
+                    let t = 1.0 - fresnel_dielectric(wo.dot(&wm), p.eta);
+                    let denom = (wi.dot(&wm) + wo.dot(&wm) / p.eta).powi(2);
+                    let f = t
+                        * distrib.d(&wm)
+                        * distrib.g(wo, wi)
+                        * (wi.dot(&wm) * wo.dot(&wm) / (cos_o * denom)).abs();
+                    p.base.mul(w_trans * f)
+                }
+                None => Color::default(),
+            };
+        }
+
+        let wm = (*wo + *wi).unit_vec();
+        let cos_d = wi.dot(&wm);
+
+        // Burley diffuse with retro reflection in the grazing angles, more the sheen
+        // the diffuse only get the light not reflected by the specular
+        let fresnel = schlick(p.specular_f0(), cos_d);
+        let fd90 = 0.5 + 2.0 * p.roughness * cos_d * cos_d;
+        let fl = schlick_weight(cos_i);
+        let fv = schlick_weight(cos_o);
+        let [fr, fg, fb] = fresnel.rgb_array();
+        let diffuse = (p.base * Color::new(1.0 - fr, 1.0 - fg, 1.0 - fb))
+            .mul((1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv) / PI);
+        let sheen_color = lerp(Color::new(1.0, 1.0, 1.0), p.tint(), 0.5);
+        let sheen = sheen_color.mul(p.sheen * schlick_weight(cos_d));
+        let mut f = (diffuse + sheen).mul(p.diffuse_weight() * cos_i);
+
+        let distrib = p.distrib();
+        let spec = fresnel.mul(distrib.d(&wm) * distrib.g(wo, wi) / (4.0 * cos_o));
+        f += spec;
+
+        if p.clearcoat > 0.0 {
+            let cc = p.clearcoat_distrib();
+            let fc = 0.04 + 0.96 * schlick_weight(wo.dot(&wm));
+            let coat = 0.25 * p.clearcoat * fc * cc.d(&wm) * cc.g(wo, wi) / (4.0 * cos_o);
+            f += Color::new(coat, coat, coat);
+        }
+
+        // total internal reflection of the transmission lobe
+        if w_trans > 0.0 && refract(wo, &wm, p.eta).is_none() {
+            let tir = distrib.d(&wm) * distrib.g(wo, wi) / (4.0 * cos_o);
+            f += p.base.mul(w_trans * tir);
+        }
+        f
+    }
+
+    fn pdf_local(p: &Params, wo: &Vec3, wi: &Vec3) -> f64 {
+        let cos_o = wo[2];
+        let cos_i = wi[2];
+        if cos_o <= 0.0 || cos_i == 0.0 {
+            return 0.0;
+        }
+        let [p_diffuse, p_spec, p_coat, p_trans] = p.lobe_probs(cos_o);
+        let distrib = p.distrib();
+
+        if cos_i < 0.0 {
+            return match Self::transmission_half(wo, wi, p.eta) {
+                Some(wm) => {
+                    let denom = (wi.dot(&wm) + wo.dot(&wm) / p.eta).powi(2);
+                    let dwm_dwi = wi.dot(&wm).abs() / denom;
+                    p_trans * distrib.d_visible(wo, &wm) * dwm_dwi
+                }
+                None => 0.0,
+            };
+        }
+
+        let wm = (*wo + *wi).unit_vec();
+        let jacobian = 4.0 * wo.dot(&wm).abs();
+        let mut pdf = p_diffuse * cos_i / PI + p_spec * distrib.d_visible(wo, &wm) / jacobian;
+        if p_coat > 0.0 {
+            pdf += p_coat * p.clearcoat_distrib().d_visible(wo, &wm) / jacobian;
+        }
+        if p_trans > 0.0 && refract(wo, &wm, p.eta).is_none() {
+            pdf += p_trans * distrib.d_visible(wo, &wm) / jacobian;
+        }
+        pdf
+    }
+
+    // microfacet normal that refract wo into wi, if it is not backfacing
+    fn transmission_half(wo: &Vec3, wi: &Vec3, eta: f64) -> Option<Vec3> {
+        let wm = wi.mul(eta) + *wo;
+        if wm.near_zero() {
+            return None;
+        }
+        let wm = wm.unit_vec();
+        let wm = if wm[2] < 0.0 { wm.mul(-1.0) } else { wm };
+        if wm.dot(wi) * wi[2] < 0.0 || wm.dot(wo) * wo[2] < 0.0 {
+            return None;
+        }
+        Some(wm)
+    }
+
+    fn local_dirs(r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> (Vec3, Vec3) {
+        let frame = Onb::new(&rec.normal);
+        (
+            frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0)),
+            frame.inverse_transform(&wi.unit_vec()),
+        )
+    }
+}
+
+impl Material for Principled {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Self {
+            base_color: self.base_color.clone(),
+            metallic: self.metallic.clone(),
+            roughness: self.roughness.clone(),
+            specular: self.specular.clone(),
+            specular_tint: self.specular_tint.clone(),
+            sheen: self.sheen.clone(),
+            clearcoat: self.clearcoat.clone(),
+            clearcoat_gloss: self.clearcoat_gloss.clone(),
+            transmission: self.transmission.clone(),
+            ior: self.ior.clone(),
+            emission: self.emission.clone(),
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        let p = self.params(rec);
+        let frame = Onb::new(&rec.normal);
+        let wo = frame.inverse_transform(&r_in.direction.unit_vec().mul(-1.0));
+        if wo[2] <= 0.0 {
+            return false;
+        }
+
+        // choose one lobe, the weight use the pdf of all of them
+        let [p_diffuse, p_spec, p_coat, _] = p.lobe_probs(wo[2]);
+        let xi = randon_f64();
+        let wi = if xi < p_diffuse {
+            random_cosine_direction()
+        } else if xi < p_diffuse + p_spec {
+            reflect(&wo, &p.distrib().sample_wm(&wo))
+        } else if xi < p_diffuse + p_spec + p_coat {
+            reflect(&wo, &p.clearcoat_distrib().sample_wm(&wo))
+        } else {
+            let wm = p.distrib().sample_wm(&wo);
+            match refract(&wo, &wm, p.eta) {
+                Some(t) if t[2] < 0.0 => t,
+                Some(_) => return false,
+                None => reflect(&wo, &wm),
+            }
+        };
+        // a reflection can't go to the other side, it would be read as transmission
+        if wi[2] == 0.0 || (wi[2] < 0.0 && xi < p_diffuse + p_spec + p_coat) {
+            return false;
+        }
+
+        let pdf = Self::pdf_local(&p, &wo, &wi);
+        if pdf <= 0.0 {
+            return false;
+        }
+        *r_ref = r_in.spawn(rec.point, frame.transform(&wi));
+        *attenuation = Self::eval_local(&p, &wo, &wi).mul(1.0 / pdf);
+        true
+    }
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.emission.value(rec.u, rec.v, &rec.point)
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        let (wo, wi) = Self::local_dirs(r_in, rec, wi);
+        Self::eval_local(&self.params(rec), &wo, &wi)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        let (wo, wi) = Self::local_dirs(r_in, rec, wi);
+        Self::pdf_local(&self.params(rec), &wo, &wi)
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    ray::{HitRecord, Hittable, Ray},
+    vec::Vec3,
+};
+
+// parallelogram with a corner in q and the sides u and v
+// the (u, v) of the hit go from 0.0 to 1.0 along the sides
+pub struct Quad {
+    q: Vec3,
+    u: Vec3,
+    v: Vec3,
+    // n / (n . n), to find the plane coordinates of the hit
+    w: Vec3,
+    normal: Vec3,
+    // plane is normal . p = d
+    d: f64,
+    mat: Box<dyn Material + Sync>,
+    bbox: Aabb,
+}
+
+impl Quad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, mat: Box<dyn Material + Sync>) -> Self {
+        let n = u * v;
+        let normal = n.unit_vec();
+        // box of the two diagonals
+        let bbox = Aabb::union(
+            &Aabb::from_points(q, q + u + v),
+            &Aabb::from_points(q + u, q + v),
+        );
+        Quad {
+            q,
+            u,
+            v,
+            w: n.div(n.dot(&n)),
+            normal,
+            d: normal.dot(&q),
+            mat,
+            bbox,
+        }
+    }
+
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(&r.direction);
+        // parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = (self.d - self.normal.dot(&r.origin)) / denom;
+        if t <= r_min || r_max <= t {
+            return false;
+        }
+
+        // coordinates of the hit in the plane, by the sides u and v
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = self.w.dot(&(planar * self.v));
+        let beta = self.w.dot(&(self.u * planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.point = p;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.tangent = self.u;
+        rec.bitangent = self.v;
+        rec.set_face_normal(r, self.normal);
+        rec.mat = self.mat.clone_box();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
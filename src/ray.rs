@@ -1,4 +1,5 @@
 use crate::{
+    aabb::Aabb,
     material::{DefaultMaterial, Material},
     vec::{self, Vec3},
 };
@@ -10,11 +11,34 @@ pub struct Ray {
     // of the ray, this function is impl in at function
     pub origin: vec::Vec3,
     pub direction: vec::Vec3,
+    // moment the ray exists, for motion blur, objects moves in [0.0, 1.0]
+    pub time: f64,
+    // wavelength(nm) of the path, for spectral effects like dispersion
+    // None while the path still carry all the rgb
+    pub wavelength: Option<f64>,
 }
 
 impl Ray {
     pub fn new(origin: vec::Vec3, direction: vec::Vec3) -> Self {
-        Ray { origin, direction }
+        Ray::new_timed(origin, direction, 0.0)
+    }
+
+    pub fn new_timed(origin: vec::Vec3, direction: vec::Vec3, time: f64) -> Self {
+        Ray {
+            origin,
+            direction,
+            time,
+            wavelength: None,
+        }
+    }
+
+    // new ray of the same path, keep the time and the wavelength
+    pub fn spawn(&self, origin: vec::Vec3, direction: vec::Vec3) -> Self {
+        Ray {
+            origin,
+            direction,
+            ..*self
+        }
     }
 
     pub fn at(&self, t: f64) -> vec::Vec3 {
@@ -27,18 +51,30 @@ pub trait Hittable: Sync {
     fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool;
     // the idea is check if the t is t_min < t < t_max and save this in HitRecord
     // there best ways to do that but i will upgrade later
+
+    // box that contains the object in all the time [0.0, 1.0]
+    fn bounding_box(&self) -> Aabb;
 }
 
 pub struct HittableList {
     objs: Vec<Box<dyn Hittable + Sync>>, // vec de objetos que tem a trait hittable
+    bbox: Aabb,
 }
 impl HittableList {
     pub fn new() -> Self {
-        HittableList { objs: Vec::new() }
+        HittableList {
+            objs: Vec::new(),
+            bbox: Aabb::default(),
+        }
     }
     pub fn add(&mut self, h: Box<dyn Hittable + Sync>) {
+        self.bbox = Aabb::union(&self.bbox, &h.bounding_box());
         self.objs.push(h);
     }
+
+    pub fn len(&self) -> usize {
+        self.objs.len()
+    }
 }
 
 impl Hittable for HittableList {
@@ -49,6 +85,8 @@ impl Hittable for HittableList {
 
         for i in &self.objs {
             // find the some hit, update the r_max finding the the clesest
+            // only the lights set the light, clean what the last one left
+            tmp_record.light = None;
             if i.hit(r, closest_so_far, r_min, &mut tmp_record) {
                 hit_any = true;
                 closest_so_far = tmp_record.t;
@@ -58,13 +96,26 @@ impl Hittable for HittableList {
 
         hit_any
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
 }
 
 pub struct HitRecord {
     pub point: Vec3,
     pub normal: Vec3,
     pub t: f64,
+    // surface coordinates of the hit, for the textures
+    pub u: f64,
+    pub v: f64,
+    // tangent frame, how the point change with u and v (dp/du and dp/dv)
+    // not unit and not flipped with the normal, zero if the object have no uv
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
     pub front_face: bool,
+    // index in the lights of the camera when the object is also a light
+    pub light: Option<usize>,
     // add material, but the trait is complex to add here
     pub mat: Box<dyn Material>,
 }
@@ -75,7 +126,12 @@ impl Clone for HitRecord {
             point: self.point,
             normal: self.normal,
             t: self.t,
+            u: self.u,
+            v: self.v,
+            tangent: self.tangent,
+            bitangent: self.bitangent,
             front_face: self.front_face,
+            light: self.light,
             // Precisamos clonar o conteúdo da Box.
             // Isso requer que o trait Material também suporte clonagem de objetos (object-safe Clone).
             mat: self.mat.clone_box(),
@@ -89,7 +145,12 @@ impl Default for HitRecord {
             point: Vec3::default(),
             normal: Vec3::default(),
             t: f64::default(),
+            u: f64::default(),
+            v: f64::default(),
+            tangent: Vec3::default(),
+            bitangent: Vec3::default(),
             front_face: bool::default(),
+            light: None,
             mat: Box::new(DefaultMaterial {}),
         }
     }
@@ -114,28 +175,87 @@ impl HitRecord {
 }
 
 pub struct Sphere {
-    center: Vec3,
+    // the center is a path, center.at(time) is where the sphere is in that time
+    // a static sphere has a zero direction
+    center: Ray,
     radius: f64,
     mat: Box<dyn Material + Sync>,
+    bbox: Aabb,
 }
 
 impl Sphere {
     pub fn new(center: Vec3, radius: f64, mat: Box<dyn Material + Sync>) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(vec::VecTypes::Coordinates, radius, radius, radius);
         Sphere {
-            center,
-            radius: radius.max(0.0),
+            center: Ray::new(center, Vec3::default()),
+            radius,
             mat,
+            bbox: Aabb::from_points(center - rvec, center + rvec),
+        }
+    }
+
+    // sphere that goes from center1 in time 0.0 to center2 in time 1.0
+    pub fn new_moving(
+        center1: Vec3,
+        center2: Vec3,
+        radius: f64,
+        mat: Box<dyn Material + Sync>,
+    ) -> Self {
+        let radius = radius.max(0.0);
+        let rvec = Vec3::new(vec::VecTypes::Coordinates, radius, radius, radius);
+        // box need to cover the sphere in the two extremes of the path
+        let box1 = Aabb::from_points(center1 - rvec, center1 + rvec);
+        let box2 = Aabb::from_points(center2 - rvec, center2 + rvec);
+        Sphere {
+            center: Ray::new(center1, center2 - center1),
+            radius,
+            mat,
+            bbox: Aabb::union(&box1, &box2),
         }
     }
 
     pub fn boxed(self) -> Box<Self> {
         Box::new(self)
     }
+
+    // p is a point in the unit sphere centered in the origin
+    // u is the angle arround y from x = -1, v the angle from y = -1, both in [0, 1]
+    fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
+        let theta = (-p[1]).acos();
+        let phi = (-p[2]).atan2(p[0]) + std::f64::consts::PI;
+        (
+            phi / (2.0 * std::f64::consts::PI),
+            theta / std::f64::consts::PI,
+        )
+    }
+
+    // derivatives of the point by u and v of get_sphere_uv, in the point p
+    // of the unit sphere, the dp/dv is zero in the poles
+    fn get_sphere_tangents(&self, p: &Vec3) -> (Vec3, Vec3) {
+        let pi = std::f64::consts::PI;
+        let dpdu =
+            Vec3::new(vec::VecTypes::Coordinates, p[2], 0.0, -p[0]).mul(2.0 * pi * self.radius);
+        let sin_theta = (p[0] * p[0] + p[2] * p[2]).sqrt();
+        let dpdv = if sin_theta > 1e-8 {
+            Vec3::new(
+                vec::VecTypes::Coordinates,
+                -p[1] * p[0] / sin_theta,
+                sin_theta,
+                -p[1] * p[2] / sin_theta,
+            )
+            .mul(pi * self.radius)
+        } else {
+            Vec3::default()
+        };
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
-        let oc = self.center - r.origin;
+        let current_center = self.center.at(r.time);
+        let oc = current_center - r.origin;
         let a = r.direction.vec_length().powi(2);
         let h = r.direction.dot(&oc);
         let c = oc.vec_length().powi(2) - self.radius.powi(2);
@@ -161,11 +281,17 @@ impl Hittable for Sphere {
         // get the closest point
         rec.t = root;
         rec.point = r.at(root);
-        let outward_normal = (rec.point - self.center).div(self.radius);
+        let outward_normal = (rec.point - current_center).div(self.radius);
         rec.set_face_normal(r, outward_normal);
+        (rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+        (rec.tangent, rec.bitangent) = self.get_sphere_tangents(&outward_normal);
 
         // can't impl copy basic for a trait
         rec.mat = self.mat.clone_box();
         true
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
 }
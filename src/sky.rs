@@ -0,0 +1,234 @@
+use std::f64::consts::PI;
+
+use crate::{
+    color::Color,
+    environment::Environment,
+    onb::Onb,
+    spectrum::xyz_to_rgb,
+    utils::{degrees_to_radians, randon_f64},
+    vec::{Vec3, VecTypes},
+};
+
+// the Preetham luminance is in kcd/m^2, this bring a sunny day to the
+// range of the rest of the renderer, a white wall in the sun is ~1.0
+const SKY_SCALE: f64 = 0.03;
+// luminance of the sun out of the atmosphere, in kcd/m^2
+const SUN_LUMINANCE: f64 = 1.6e6;
+// angular radius of the sun seen from earth
+const SUN_RADIUS: f64 = 0.2665;
+// wavelengths of the red, green and blue, in micrometers, for the sun extinction
+const RGB_LAMBDAS: [f64; 3] = [0.680, 0.550, 0.440];
+
+// Perez distribution, coefficients linear in the turbidity
+#[derive(Debug, Clone, Copy)]
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    // theta the zenith angle of the view, gamma the angle to the sun
+    fn f(&self, theta: f64, gamma: f64) -> f64 {
+        let cos_theta = theta.cos().max(0.01);
+        (1.0 + self.a * (self.b / cos_theta).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * gamma.cos().powi(2))
+    }
+}
+
+// analytic daylight sky of Preetham, Shirley and Smits (1999), with the sun
+// as a small disk that can be sampled by itself, like a environment
+// elevation and azimuth in degrees, the azimuth 0.0 is to -z and 90.0 to +x
+// turbidity is the haze, 2.0 is very clear and 10.0 is hazy
+// below the horizon there is no light, the scenes should have a ground
+pub struct Sky {
+    sun_dir: Vec3,
+    theta_sun: f64,
+    perez: [Perez; 3],
+    // luminance and chromaticity in the zenith
+    zenith: [f64; 3],
+    sun_radiance: Color,
+    cos_sun: f64,
+    intensity: f64,
+}
+
+impl Sky {
+    pub fn new(elevation: f64, azimuth: f64, turbidity: f64) -> Self {
+        let t = turbidity.clamp(1.7, 10.0);
+        // the model is not defined with the sun below the horizon
+        let elevation = degrees_to_radians(elevation.clamp(-90.0, 90.0));
+        let azimuth = degrees_to_radians(azimuth);
+        let sun_dir = Vec3::new(
+            VecTypes::Coordinates,
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        );
+        let theta_sun = (PI / 2.0 - elevation).clamp(0.0, PI / 2.0 - 1e-3);
+
+        let perez = [
+            Perez {
+                a: 0.1787 * t - 1.4630,
+                b: -0.3554 * t + 0.4275,
+                c: -0.0227 * t + 5.3251,
+                d: 0.1206 * t - 2.5771,
+                e: -0.0670 * t + 0.3703,
+            },
+            Perez {
+                a: -0.0193 * t - 0.2592,
+                b: -0.0665 * t + 0.0008,
+                c: -0.0004 * t + 0.2125,
+                d: -0.0641 * t - 0.8989,
+                e: -0.0033 * t + 0.0452,
+            },
+            Perez {
+                a: -0.0167 * t - 0.2608,
+                b: -0.0950 * t + 0.0092,
+                c: -0.0079 * t + 0.2102,
+                d: -0.0441 * t - 1.6537,
+                e: -0.0109 * t + 0.0529,
+            },
+        ];
+
+        let th = theta_sun;
+        let (th2, th3) = (th * th, th * th * th);
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * th);
+        let y_z = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let x_z = t * t * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let yy_z = t * t * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        let mut sky = Sky {
+            sun_dir,
+            theta_sun,
+            perez,
+            zenith: [y_z.max(0.0), x_z, yy_z],
+            sun_radiance: Color::default(),
+            cos_sun: 1.0,
+            intensity: 1.0,
+        };
+        sky.sun_radiance = sky.sun_color(turbidity, elevation);
+        sky.with_sun_size(SUN_RADIUS)
+    }
+
+    // bigger sun give softer shadows, the light of the sun is the same
+    pub fn with_sun_size(mut self, radius_degrees: f64) -> Self {
+        let old = 1.0 - self.cos_sun;
+        self.cos_sun = degrees_to_radians(radius_degrees.clamp(1e-3, 45.0)).cos();
+        if old > 0.0 {
+            self.sun_radiance = self.sun_radiance.mul(old / (1.0 - self.cos_sun));
+        }
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    // sun after cross the atmosphere, Rayleigh and aerosol extinction(Preetham)
+    // for the real size of the sun, with_sun_size keep the irradiance
+    fn sun_color(&self, turbidity: f64, elevation: f64) -> Color {
+        if elevation <= 0.0 {
+            return Color::default();
+        }
+        let theta_deg = 90.0 - elevation.to_degrees();
+        // relative optical mass of the air
+        let m = 1.0 / (elevation.sin() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+        let beta = 0.04608 * turbidity - 0.04586;
+        let tr = RGB_LAMBDAS.map(|l: f64| {
+            let rayleigh = (-0.008735 * l.powf(-4.08) * m).exp();
+            let aerosol = (-beta * l.powf(-1.3) * m).exp();
+            rayleigh * aerosol
+        });
+        Color::new(tr[0], tr[1], tr[2]).mul(SUN_LUMINANCE * SKY_SCALE)
+    }
+
+    // the sky without the sun disk
+    fn sky_radiance(&self, dir: &Vec3) -> Color {
+        if dir[1] <= 0.0 {
+            return Color::default();
+        }
+        let theta = dir[1].clamp(-1.0, 1.0).acos();
+        let gamma = dir.dot(&self.sun_dir).clamp(-1.0, 1.0).acos();
+        let ratio = |i: usize| self.perez[i].f(theta, gamma) / self.perez[i].f(0.0, self.theta_sun);
+        let y = self.zenith[0] * ratio(0) * SKY_SCALE;
+        let x = self.zenith[1] * ratio(1);
+        let yy = self.zenith[2] * ratio(2);
+        if yy <= 0.0 {
+            return Color::default();
+        }
+        // xyY to XYZ to linear sRGB
+        let rgb = xyz_to_rgb([x / yy * y, y, (1.0 - x - yy) / yy * y]);
+        Color::new(rgb[0].max(0.0), rgb[1].max(0.0), rgb[2].max(0.0))
+    }
+
+    fn in_sun(&self, dir: &Vec3) -> bool {
+        dir.dot(&self.sun_dir) >= self.cos_sun
+    }
+
+    // probability of sample the sun and not the sky
+    fn sun_prob(&self) -> f64 {
+        if self.sun_dir[1] > 0.0 { 0.5 } else { 0.0 }
+    }
+
+    fn sun_pdf(&self) -> f64 {
+        1.0 / (2.0 * PI * (1.0 - self.cos_sun))
+    }
+}
+
+impl Environment for Sky {
+    fn radiance(&self, dir: &Vec3) -> Color {
+        let d = dir.unit_vec();
+        let mut l = self.sky_radiance(&d);
+        if self.in_sun(&d) && d[1] > 0.0 {
+            l += self.sun_radiance;
+        }
+        l.mul(self.intensity)
+    }
+
+    // the sun cone or the sky by the cosine of the zenith
+    fn sample(&self) -> Option<(Vec3, Color, f64)> {
+        let dir = if randon_f64() < self.sun_prob() {
+            let cos = 1.0 - randon_f64() * (1.0 - self.cos_sun);
+            let sin = (1.0 - cos * cos).max(0.0).sqrt();
+            let phi = 2.0 * PI * randon_f64();
+            let frame = Onb::new(&self.sun_dir);
+            frame.transform(&Vec3::new(
+                VecTypes::Coordinates,
+                sin * phi.cos(),
+                sin * phi.sin(),
+                cos,
+            ))
+        } else {
+            let r = randon_f64().sqrt();
+            let phi = 2.0 * PI * randon_f64();
+            let z = (1.0 - r * r).max(0.0).sqrt();
+            let frame = Onb::new(&Vec3::new(VecTypes::Coordinates, 0.0, 1.0, 0.0));
+            frame.transform(&Vec3::new(
+                VecTypes::Coordinates,
+                r * phi.cos(),
+                r * phi.sin(),
+                z,
+            ))
+        };
+        let pdf = self.pdf(&dir);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some((dir, self.radiance(&dir), pdf))
+    }
+
+    fn pdf(&self, dir: &Vec3) -> f64 {
+        let d = dir.unit_vec();
+        let p_sun = self.sun_prob();
+        let sun = if self.in_sun(&d) { self.sun_pdf() } else { 0.0 };
+        let sky = d[1].max(0.0) / PI;
+        p_sun * sun + (1.0 - p_sun) * sky
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    ops::{Add, Mul},
+    sync::OnceLock,
+};
+
+use crate::utils::random_Interval_f64;
+
+// visible range used by the spectral paths, in nanometers
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+pub fn sample_wavelength() -> f64 {
+    random_Interval_f64(LAMBDA_MIN, LAMBDA_MAX)
+}
+
+// piecewise gaussian, diferent width in each side of the mean
+fn g(x: f64, mu: f64, sigma1: f64, sigma2: f64) -> f64 {
+    let t = (x - mu) / if x < mu { sigma1 } else { sigma2 };
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 2 degrees color matching functions, using the multi-lobe fit
+// of Wyman, Sloan and Shirley (2013), good enough without carry the tables
+pub fn cie_xyz(lambda: f64) -> [f64; 3] {
+    let x = 1.056 * g(lambda, 599.8, 37.9, 31.0) + 0.362 * g(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * g(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * g(lambda, 568.8, 46.9, 40.5) + 0.286 * g(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * g(lambda, 437.0, 11.8, 36.0) + 0.681 * g(lambda, 459.0, 26.0, 13.8);
+    [x, y, z]
+}
+
+// XYZ to linear sRGB (D65)
+pub fn xyz_to_rgb(xyz: [f64; 3]) -> [f64; 3] {
+    let [x, y, z] = xyz;
+    [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.9692660 * x + 1.8760108 * y + 0.0415560 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ]
+}
+
+// mean of the rgb of each wavelength in the visible range, used to make
+// a white path stay white when the wavelength is choosed uniformly
+fn rgb_mean() -> &'static [f64; 3] {
+    static MEAN: OnceLock<[f64; 3]> = OnceLock::new();
+    MEAN.get_or_init(|| {
+        let steps = 4000;
+        let mut accum = [0.0; 3];
+        for i in 0..steps {
+            let lambda = LAMBDA_MIN + (i as f64 + 0.5) * (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+            let rgb = xyz_to_rgb(cie_xyz(lambda));
+            for c in 0..3 {
+                accum[c] += rgb[c] / steps as f64;
+            }
+        }
+        accum
+    })
+}
+
+// rgb weight of a single wavelength, the mean in the visible range is (1, 1, 1)
+// the values can be negative, out of sRGB gamut, but the mean of many is fine
+pub fn wavelength_to_rgb(lambda: f64) -> [f64; 3] {
+    let rgb = xyz_to_rgb(cie_xyz(lambda));
+    let mean = rgb_mean();
+    [rgb[0] / mean[0], rgb[1] / mean[1], rgb[2] / mean[2]]
+}
+
+// integral of the y matching function, normalize the film so a spectrum
+// of constant 1.0 have luminance 1.0
+fn cie_y_integral() -> f64 {
+    static Y: OnceLock<f64> = OnceLock::new();
+    *Y.get_or_init(|| {
+        let mut accum = 0.0;
+        let mut lambda = LAMBDA_MIN;
+        while lambda <= LAMBDA_MAX {
+            accum += cie_xyz(lambda)[1];
+            lambda += 1.0;
+        }
+        accum
+    })
+}
+
+// amount of wavelengths traced by each path, the first is the hero
+pub const N_SAMPLES: usize = 4;
+
+// the wavelengths of a path, hero and the others equally spaced in the range
+#[derive(Debug, Clone, Copy)]
+pub struct SampledWavelengths {
+    pub lambda: [f64; N_SAMPLES],
+    pub pdf: [f64; N_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample_uniform() -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = sample_wavelength();
+        let mut lambda = [hero; N_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            // rotate the hero, wrapping in the range
+            *l = hero + i as f64 * range / N_SAMPLES as f64;
+            if *l > LAMBDA_MAX {
+                *l -= range;
+            }
+        }
+        SampledWavelengths {
+            lambda,
+            pdf: [1.0 / range; N_SAMPLES],
+        }
+    }
+
+    // a dispersive surface split the wavelengths, so only the hero keep going
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for p in self.pdf.iter_mut().skip(1) {
+            *p = 0.0;
+        }
+        self.pdf[0] /= N_SAMPLES as f64;
+    }
+
+    pub fn secondary_terminated(&self) -> bool {
+        self.pdf[1..].iter().all(|p| *p == 0.0)
+    }
+}
+
+// values of a spectrum in the sampled wavelengths
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SampledSpectrum {
+    pub values: [f64; N_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn from_fn<F: Fn(f64) -> f64>(lambdas: &SampledWavelengths, f: F) -> Self {
+        let mut values = [0.0; N_SAMPLES];
+        for (v, l) in values.iter_mut().zip(lambdas.lambda) {
+            *v = f(l);
+        }
+        SampledSpectrum { values }
+    }
+
+    // reflectance of a rgb albedo
+    pub fn from_albedo(rgb: [f64; 3], lambdas: &SampledWavelengths) -> Self {
+        Self::from_fn(lambdas, |l| rgb_to_spectrum(rgb, l))
+    }
+
+    // light of a rgb color, the white is the D65
+    pub fn from_emission(rgb: [f64; 3], lambdas: &SampledWavelengths) -> Self {
+        Self::from_fn(lambdas, |l| {
+            rgb_to_spectrum(rgb, l) * Illuminant::D65.value(l)
+        })
+    }
+
+    // monte carlo estimate of the XYZ of the spectrum, then to linear sRGB
+    pub fn to_rgb(self, lambdas: &SampledWavelengths) -> [f64; 3] {
+        let mut xyz = [0.0; 3];
+        for i in 0..N_SAMPLES {
+            if lambdas.pdf[i] == 0.0 {
+                continue;
+            }
+            let cmf = cie_xyz(lambdas.lambda[i]);
+            for c in 0..3 {
+                xyz[c] += cmf[c] * self.values[i] / lambdas.pdf[i];
+            }
+        }
+        let scale = 1.0 / (N_SAMPLES as f64 * cie_y_integral());
+        xyz_to_rgb([xyz[0] * scale, xyz[1] * scale, xyz[2] * scale])
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut values = self.values;
+        for (v, r) in values.iter_mut().zip(rhs.values) {
+            *v += r;
+        }
+        SampledSpectrum { values }
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut values = self.values;
+        for (v, r) in values.iter_mut().zip(rhs.values) {
+            *v *= r;
+        }
+        SampledSpectrum { values }
+    }
+}
+
+fn smoothstep(x: f64, edge0: f64, edge1: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// smooth spectrum for a rgb, a blue, green and red band that sum 1.0 in
+// all wavelengths, so white is constant 1.0 and albedos stay in [0.0, 1.0]
+pub fn rgb_to_spectrum(rgb: [f64; 3], lambda: f64) -> f64 {
+    let b = 1.0 - smoothstep(lambda, 475.0, 505.0);
+    let r = smoothstep(lambda, 570.0, 600.0);
+    let g = 1.0 - b - r;
+    (rgb[0] * r + rgb[1] * g + rgb[2] * b).max(0.0)
+}
+
+// CIE D65 from 380nm to 780nm, in steps of 10nm
+const D65: [f64; 41] = [
+    49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861,
+    115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100.0, 96.3342, 95.788,
+    88.6856, 90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842,
+    69.7213, 71.6091, 74.349, 61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054, 63.3828,
+];
+
+// CIE F2 from 380nm to 780nm, in steps of 5nm, the mercury lines are in
+// the bins of their wavelengths
+const F2: [f64; 81] = [
+    1.18, 1.48, 1.84, 2.15, 3.44, 15.69, 3.85, 3.74, 4.19, 4.62, 5.06, 34.98, 11.81, 6.27, 6.63,
+    6.93, 7.19, 7.4, 7.54, 7.62, 7.65, 7.62, 7.62, 7.45, 7.28, 7.15, 7.05, 7.04, 7.16, 7.47, 8.04,
+    8.88, 10.01, 24.88, 16.64, 14.59, 16.16, 17.56, 18.62, 21.47, 22.79, 19.29, 18.66, 17.73,
+    16.54, 15.21, 13.8, 12.36, 10.95, 9.65, 8.4, 7.32, 6.31, 5.43, 4.68, 4.02, 3.45, 2.96, 2.55,
+    2.19, 1.89, 1.64, 1.53, 1.27, 1.1, 0.99, 0.88, 0.76, 0.68, 0.61, 0.56, 0.54, 0.51, 0.47, 0.47,
+    0.43, 0.46, 0.47, 0.4, 0.33, 0.27,
+];
+
+// CIE F11 from 380nm to 780nm, in steps of 5nm
+const F11: [f64; 81] = [
+    0.91, 0.63, 0.46, 0.37, 1.29, 12.68, 1.59, 1.79, 2.46, 3.33, 4.49, 33.94, 12.13, 6.95, 7.19,
+    7.12, 6.72, 6.13, 5.46, 4.79, 5.66, 14.29, 14.96, 8.97, 4.72, 2.33, 1.47, 1.1, 0.89, 0.83,
+    1.18, 4.9, 39.59, 72.84, 32.61, 7.52, 2.83, 1.96, 1.67, 4.43, 11.28, 14.76, 12.73, 9.74, 7.33,
+    9.72, 55.27, 42.58, 13.18, 13.16, 12.26, 5.11, 2.07, 2.34, 3.58, 3.01, 2.48, 2.14, 1.54, 1.33,
+    1.46, 1.94, 2.0, 1.2, 1.35, 4.1, 5.58, 2.51, 0.57, 0.27, 0.23, 0.21, 0.24, 0.24, 0.2, 0.24,
+    0.32, 0.26, 0.16, 0.12, 0.09,
+];
+
+// linear interpolation of a table from LAMBDA_MIN to LAMBDA_MAX in equal steps
+fn table(values: &[f64], lambda: f64) -> f64 {
+    let last = values.len() - 1;
+    let x =
+        ((lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * last as f64).clamp(0.0, last as f64);
+    let i = (x.floor() as usize).min(last - 1);
+    let f = x - i as f64;
+    values[i] * (1.0 - f) + values[i + 1] * f
+}
+
+// spectrum of the light sources, all normalized to luminance 1.0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Illuminant {
+    // daylight, the white of sRGB
+    #[default]
+    D65,
+    // incandescent lamp, a blackbody in 2856K
+    A,
+    // equal energy
+    E,
+    // cool white fluorescent, a continuum plus the mercury lines
+    F2,
+    // narrow tri-band fluorescent
+    F11,
+}
+
+impl Illuminant {
+    const ALL: [Illuminant; 5] = [
+        Illuminant::D65,
+        Illuminant::A,
+        Illuminant::E,
+        Illuminant::F2,
+        Illuminant::F11,
+    ];
+
+    // relative power, not normalized
+    fn raw(&self, lambda: f64) -> f64 {
+        match self {
+            Illuminant::D65 => table(&D65, lambda),
+            Illuminant::A => {
+                // Planck law, lambda in meters
+                let l = lambda * 1e-9;
+                let c2 = 1.4388e-2;
+                1.0 / (l.powi(5) * ((c2 / (l * 2856.0)).exp() - 1.0))
+            }
+            Illuminant::E => 1.0,
+            Illuminant::F2 => table(&F2, lambda),
+            Illuminant::F11 => table(&F11, lambda),
+        }
+    }
+
+    fn norm(&self) -> f64 {
+        static NORMS: OnceLock<[f64; 5]> = OnceLock::new();
+        let norms = NORMS.get_or_init(|| {
+            Illuminant::ALL.map(|ill| {
+                let mut accum = 0.0;
+                let mut lambda = LAMBDA_MIN;
+                while lambda <= LAMBDA_MAX {
+                    accum += ill.raw(lambda) * cie_xyz(lambda)[1];
+                    lambda += 1.0;
+                }
+                cie_y_integral() / accum
+            })
+        });
+        norms[Illuminant::ALL.iter().position(|i| i == self).unwrap()]
+    }
+
+    // power in the wavelength, scaled to luminance 1.0
+    pub fn value(&self, lambda: f64) -> f64 {
+        self.raw(lambda) * self.norm()
+    }
+
+    // linear sRGB of the illuminant, for the rgb mode
+    pub fn to_rgb(self) -> [f64; 3] {
+        let mut xyz = [0.0; 3];
+        let mut lambda = LAMBDA_MIN;
+        while lambda <= LAMBDA_MAX {
+            let cmf = cie_xyz(lambda);
+            for c in 0..3 {
+                xyz[c] += cmf[c] * self.value(lambda);
+            }
+            lambda += 1.0;
+        }
+        let y = cie_y_integral();
+        xyz_to_rgb([xyz[0] / y, xyz[1] / y, xyz[2] / y])
+    }
+}
@@ -0,0 +1,267 @@
+use std::f64::consts::PI;
+
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    material::{Dieletric, Material},
+    medium::sample_distance,
+    microfacet::{fresnel_dielectric, reflect},
+    phase::{Isotropic, PhaseFunction},
+    ray::{HitRecord, Hittable, Ray},
+    utils::{INF, randon_f64},
+    vec::Vec3,
+};
+
+// the walk inside has its own bound, not the max deep of the camera, and is
+// ended by the russian roulette after some scatters
+const MAX_WALK: u32 = 1024;
+const ROULETTE_WALK: u32 = 8;
+
+// where the walk leave the object, the light go out through the boundary in
+// a diffuse lobe, the transmission of the Fresnel normalized(like the Sw of
+// pbrt), so the light sampling reach the inside by this point
+// weight is the throughput of the walk
+struct Exit {
+    weight: Color,
+    ior: f64,
+    // mean of the transmission, over the cosine hemisphere
+    norm: f64,
+}
+
+impl Exit {
+    fn lobe(&self, cos: f64) -> Color {
+        self.weight
+            .mul((1.0 - fresnel_dielectric(cos, self.ior)) / self.norm)
+    }
+}
+
+impl Material for Exit {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(Exit {
+            weight: self.weight,
+            ior: self.ior,
+            norm: self.norm,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        // the walk was absorbed inside
+        if self.weight.rgb_array() == [0.0; 3] {
+            return false;
+        }
+        let mut dir = rec.normal + Vec3::random_unit_vec();
+        if dir.near_zero() {
+            dir = rec.normal;
+        }
+        *r_ref = r_in.spawn(rec.point, dir);
+        *attenuation = self.lobe(rec.normal.dot(&dir.unit_vec()));
+        true
+    }
+    // the sample above is cosine weighted, so the pdf is cos / pi
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        let cos = rec.normal.dot(&wi.unit_vec());
+        if cos <= 0.0 {
+            return Color::default();
+        }
+        self.lobe(cos).mul(cos / PI)
+    }
+    fn pdf(&self, _r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        rec.normal.dot(&wi.unit_vec()).max(0.0) / PI
+    }
+}
+
+// translucent object, like skin, wax, marble or milk, the light enter by a
+// dieletric boundary and do a random walk scattering inside the closed shape
+// until leave it in other point, the mean free path is the mean distance
+// between the scatters by channel, so the red walk more than the blue in skin
+// all the walk is done in the hit of a ray inside, that only return where it
+// leave, so a path spend only one bounce of the camera in the inside
+pub struct Subsurface {
+    boundary: Box<dyn Hittable + Sync>,
+    surface: Dieletric,
+    ior: f64,
+    // scattering albedo and extinction(1 / mean free path) by channel
+    albedo: Color,
+    sigma_t: Color,
+    phase: Box<dyn PhaseFunction>,
+    // mean of the transmission of the boundary, to normalize the exit
+    exit_norm: f64,
+}
+
+impl Subsurface {
+    pub fn new(
+        boundary: Box<dyn Hittable + Sync>,
+        albedo: Color,
+        mean_free_path: Color,
+        ior: f64,
+    ) -> Self {
+        Self::new_with_phase(
+            boundary,
+            albedo,
+            mean_free_path,
+            ior,
+            Box::new(Isotropic {}),
+        )
+    }
+
+    pub fn new_with_phase(
+        boundary: Box<dyn Hittable + Sync>,
+        albedo: Color,
+        mean_free_path: Color,
+        ior: f64,
+        phase: Box<dyn PhaseFunction>,
+    ) -> Self {
+        let sigma = |mfp: f64| 1.0 / mfp.max(1e-6);
+        // the midpoint rule in cos², the hemisphere weighted by the cosine
+        let n = 64;
+        let exit_norm = (0..n)
+            .map(|i| 1.0 - fresnel_dielectric(((i as f64 + 0.5) / n as f64).sqrt(), ior))
+            .sum::<f64>()
+            / n as f64;
+        Subsurface {
+            boundary,
+            surface: Dieletric::new(ior),
+            ior,
+            albedo,
+            sigma_t: Color::new(
+                sigma(mean_free_path.rgb[0]),
+                sigma(mean_free_path.rgb[1]),
+                sigma(mean_free_path.rgb[2]),
+            ),
+            phase,
+            exit_norm,
+        }
+    }
+
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+
+    fn transmittance(&self, dist: f64) -> Color {
+        Color::new(
+            (-self.sigma_t.rgb[0] * dist).exp(),
+            (-self.sigma_t.rgb[1] * dist).exp(),
+            (-self.sigma_t.rgb[2] * dist).exp(),
+        )
+    }
+
+    // distance to the next scatter, by a channel choosed at random, so the
+    // weight is by the mean pdf of the 3 channels and the walk is unbiased
+    // with a diferent mean free path by channel
+    fn sample_distance(&self) -> f64 {
+        let channel = ((randon_f64() * 3.0) as usize).min(2);
+        sample_distance(self.sigma_t.rgb[channel])
+    }
+
+    // the walk from a point inside, going in dir(unit) with the first scatter
+    // at dist and the boundary hit in b, the exit where the walk leave, with
+    // the weight of all the walk, None if it is absorbed
+    fn walk(
+        &self,
+        r: &Ray,
+        mut dir: Vec3,
+        mut dist: f64,
+        mut b: HitRecord,
+    ) -> Option<(HitRecord, Color)> {
+        let mut weight = Color::new(1.0, 1.0, 1.0);
+        let mut point = r.origin;
+        for walk in 0..MAX_WALK {
+            let dist_boundary = (b.point - point).vec_length();
+            if dist < dist_boundary {
+                let tr = self.transmittance(dist);
+                let pdf = (0..3).map(|c| self.sigma_t.rgb[c] * tr.rgb[c]).sum::<f64>() / 3.0;
+                weight = weight * self.albedo * self.sigma_t * tr.mul(1.0 / pdf);
+                point += dir.mul(dist);
+                // the sample of the phase follow exactly its value
+                dir = self.phase.sample(&dir).0.unit_vec();
+            } else {
+                let tr = self.transmittance(dist_boundary);
+                let prob = (tr.rgb[0] + tr.rgb[1] + tr.rgb[2]) / 3.0;
+                weight = weight * tr.mul(1.0 / prob);
+                point = b.point;
+                // the normal of the back face point to the inside
+                let cos = -dir.dot(&b.normal);
+                if randon_f64() >= fresnel_dielectric(cos, 1.0 / self.ior) {
+                    return Some((b, weight));
+                }
+                dir = reflect(&dir.mul(-1.0), &b.normal);
+            }
+
+            if walk >= ROULETTE_WALK {
+                let q = weight.rgb_array().into_iter().fold(0.0, f64::max).min(0.95);
+                if randon_f64() >= q {
+                    return None;
+                }
+                weight = weight.mul(1.0 / q);
+            }
+            b = HitRecord::default();
+            if !self.boundary.hit(&r.spawn(point, dir), INF, 0.001, &mut b) {
+                return None;
+            }
+            dist = self.sample_distance();
+        }
+        None
+    }
+}
+
+impl Hittable for Subsurface {
+    fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
+        // the next boundary in all the ray, even after r_max, to know if
+        // the ray is inside(hit the back face) or outside
+        let mut b = HitRecord::default();
+        if !self.boundary.hit(r, INF, r_min, &mut b) {
+            return false;
+        }
+
+        // outside, only the dieletric boundary
+        if b.front_face {
+            if b.t > r_max {
+                return false;
+            }
+            *rec = b;
+            rec.mat = self.surface.clone_box();
+            return true;
+        }
+
+        // inside, the first event is where the ray hit, another object
+        // nearer than it is hit first
+        let ray_len = r.direction.vec_length();
+        let dist = self.sample_distance();
+        let t = (dist / ray_len).min(b.t);
+        if t > r_max {
+            return false;
+        }
+        let dir = r.direction.unit_vec();
+        let weight = match self.walk(r, dir, dist, b) {
+            Some((exit, weight)) => {
+                *rec = exit;
+                weight
+            }
+            // absorbed, an exit without light
+            None => {
+                rec.point = r.origin;
+                Color::default()
+            }
+        };
+        // the point is where the walk leave, the t only order this hit with
+        // the others in the ray, seen from outside
+        rec.t = t.max(r_min);
+        rec.normal = rec.normal.mul(-1.0);
+        rec.front_face = true;
+        rec.mat = Box::new(Exit {
+            weight,
+            ior: self.ior,
+            norm: self.exit_norm,
+        });
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
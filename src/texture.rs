@@ -0,0 +1,158 @@
+use std::{path::Path, sync::Arc};
+
+use image::{ImageResult, RgbImage};
+
+use crate::{color::Color, perlin::Perlin, vec::Vec3};
+
+// color that change along the surface, by the (u, v) of the hit
+// or by the point in the space for the procedural ones
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Color;
+
+    // for the parameters that are a single number, like roughness
+    fn scalar(&self, u: f64, v: f64, p: &Vec3) -> f64 {
+        let c = self.value(u, v, p);
+        (c.rgb[0] + c.rgb[1] + c.rgb[2]) / 3.0
+    }
+}
+
+// textures are shared, the materials are cloned in each hit
+pub type TextureRef = Arc<dyn Texture>;
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Vec3) -> Color {
+        self.albedo
+    }
+}
+
+pub fn solid(albedo: Color) -> TextureRef {
+    Arc::new(SolidColor::new(albedo))
+}
+
+// same value in the 3 channels, for the scalar parameters
+pub fn constant(value: f64) -> TextureRef {
+    solid(Color::new(value, value, value))
+}
+
+// 3d checker, scale is the size of each cell
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: TextureRef,
+    odd: TextureRef,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: TextureRef, odd: TextureRef) -> Self {
+        Self {
+            inv_scale: 1.0 / scale,
+            even,
+            odd,
+        }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Color {
+        let sum: i64 = (0..3).map(|i| (self.inv_scale * p[i]).floor() as i64).sum();
+        if sum % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// checker in the (u, v) space, count cells in each direction
+pub struct UvChecker {
+    count: f64,
+    even: TextureRef,
+    odd: TextureRef,
+}
+
+impl UvChecker {
+    pub fn new(count: f64, even: TextureRef, odd: TextureRef) -> Self {
+        Self { count, even, odd }
+    }
+}
+
+impl Texture for UvChecker {
+    fn value(&self, u: f64, v: f64, p: &Vec3) -> Color {
+        let sum = (u * self.count).floor() as i64 + (v * self.count).floor() as i64;
+        if sum % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// image loaded by the image crate, the values are decoded from the gamma 2
+// that is used when the render is saved
+pub struct ImageTexture {
+    image: RgbImage,
+}
+
+impl ImageTexture {
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        Ok(Self {
+            image: image::open(path)?.to_rgb8(),
+        })
+    }
+
+    pub fn from_image(image: RgbImage) -> Self {
+        Self { image }
+    }
+
+    // pixel in the (u, v), without the gamma decoding
+    pub fn raw(&self, u: f64, v: f64) -> Color {
+        if self.image.width() == 0 || self.image.height() == 0 {
+            // debug color, like the book
+            return Color::new(0.0, 1.0, 1.0);
+        }
+        let u = u.clamp(0.0, 1.0);
+        // image have y to bottom
+        let v = 1.0 - v.clamp(0.0, 1.0);
+        let i = ((u * self.image.width() as f64) as u32).min(self.image.width() - 1);
+        let j = ((v * self.image.height() as f64) as u32).min(self.image.height() - 1);
+        Color::from(*self.image.get_pixel(i, j))
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Vec3) -> Color {
+        let c = self.raw(u, v);
+        c * c
+    }
+}
+
+// marble like texture of the book, Perlin turbulence in sin stripes
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64) -> Self {
+        Self {
+            noise: Perlin::new(),
+            scale,
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Vec3) -> Color {
+        let t = 1.0 + (self.scale * p[2] + 10.0 * self.noise.turb(p, 7)).sin();
+        Color::new(0.5, 0.5, 0.5).mul(t)
+    }
+}
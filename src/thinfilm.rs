@@ -0,0 +1,232 @@
+use std::{
+    f64::consts::PI,
+    ops::{Add, Div, Mul, Sub},
+    sync::OnceLock,
+};
+
+use crate::{
+    color::Color,
+    ray::HitRecord,
+    spectrum::{self, LAMBDA_MAX, LAMBDA_MIN},
+    texture::{self, TextureRef},
+};
+
+// small complex number, only what the Fresnel of the layers need
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn real(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+
+    fn norm2(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // principal root, the imaginary part is never negative, so a evanescent
+    // wave decay and not grow
+    fn sqrt(self) -> Self {
+        let r = self.norm2().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    // e^(i z)
+    fn exp_i(self) -> Self {
+        let m = (-self.im).exp();
+        Self::new(m * self.re.cos(), m * self.re.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self::Output {
+        let d = rhs.norm2();
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / d,
+            (self.im * rhs.re - self.re * rhs.im) / d,
+        )
+    }
+}
+
+// amplitude reflection between two media, s and p polarizations
+fn r_s(n_a: Complex, cos_a: Complex, n_b: Complex, cos_b: Complex) -> Complex {
+    (n_a * cos_a - n_b * cos_b) / (n_a * cos_a + n_b * cos_b)
+}
+
+fn r_p(n_a: Complex, cos_a: Complex, n_b: Complex, cos_b: Complex) -> Complex {
+    (n_b * cos_a - n_a * cos_b) / (n_b * cos_a + n_a * cos_b)
+}
+
+// wavelengths used to integrate the film in the rgb mode
+const N_LAMBDAS: usize = 32;
+
+fn rgb_table() -> &'static [(f64, [f64; 3]); N_LAMBDAS] {
+    static TABLE: OnceLock<[(f64, [f64; 3]); N_LAMBDAS]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let lambda =
+                LAMBDA_MIN + (i as f64 + 0.5) * (LAMBDA_MAX - LAMBDA_MIN) / N_LAMBDAS as f64;
+            (lambda, spectrum::wavelength_to_rgb(lambda))
+        })
+    })
+}
+
+// thin transparent layer over a surface, like soap, oil over water or the
+// shell of a beetle, the light reflected in the top and in the bottom of the
+// film interfere, so the reflectance change with the wavelength and the angle
+// thickness is in nanometers, the colors show between ~100nm and ~1000nm
+#[derive(Clone)]
+pub struct ThinFilm {
+    thickness: TextureRef,
+    min_thickness: f64,
+    max_thickness: f64,
+    ior: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, ior: f64) -> Self {
+        Self::textured(texture::constant(1.0), 0.0, thickness, ior)
+    }
+
+    // the scalar of the texture in [0.0, 1.0] go from min to max thickness
+    pub fn textured(thickness: TextureRef, min: f64, max: f64, ior: f64) -> Self {
+        Self {
+            thickness,
+            min_thickness: min,
+            max_thickness: max,
+            ior,
+        }
+    }
+
+    pub fn thickness(&self, rec: &HitRecord) -> f64 {
+        let t = self
+            .thickness
+            .scalar(rec.u, rec.v, &rec.point)
+            .clamp(0.0, 1.0);
+        self.min_thickness + t * (self.max_thickness - self.min_thickness)
+    }
+
+    // reflectance of the film for a single wavelength, the light come from
+    // a medium of index eta_i with cos_i, and the substrate is eta + i k
+    pub fn reflectance(
+        &self,
+        cos_i: f64,
+        thickness: f64,
+        eta_i: f64,
+        eta: f64,
+        k: f64,
+        lambda: f64,
+    ) -> f64 {
+        let cos_i = cos_i.clamp(0.0, 1.0);
+        let sin2_i = 1.0 - cos_i * cos_i;
+        let n0 = Complex::real(eta_i);
+        let n1 = Complex::real(self.ior);
+        let n2 = Complex::new(eta, k);
+
+        // Snell in each layer, n0 sin0 = n sin, with complex cos if needed
+        let cos_in = |n: Complex| {
+            let s = Complex::real(eta_i * eta_i * sin2_i) / (n * n);
+            (Complex::real(1.0) - s).sqrt()
+        };
+        let c0 = Complex::real(cos_i);
+        let c1 = cos_in(n1);
+        let c2 = cos_in(n2);
+
+        // phase of the round trip inside the film
+        let delta = Complex::real(4.0 * PI * thickness / lambda) * n1 * c1;
+        let phase = delta.exp_i();
+
+        // Airy sum of all the internal reflections
+        let airy = |r01: Complex, r12: Complex| {
+            let r = (r01 + r12 * phase) / (Complex::real(1.0) + r01 * r12 * phase);
+            r.norm2()
+        };
+        let rs = airy(r_s(n0, c0, n1, c1), r_s(n1, c1, n2, c2));
+        let rp = airy(r_p(n0, c0, n1, c1), r_p(n1, c1, n2, c2));
+        (0.5 * (rs + rp)).clamp(0.0, 1.0)
+    }
+
+    // the film seen in rgb, the spectral reflectance integrated by the color
+    // matching of each channel, so a path without wavelength get the right hue
+    // the substrate index by channel is spread in the spectrum like the albedos
+    pub fn reflectance_rgb(
+        &self,
+        cos_i: f64,
+        thickness: f64,
+        eta_i: f64,
+        eta: Color,
+        k: Color,
+    ) -> Color {
+        let mut accum = [0.0; 3];
+        for (lambda, weight) in rgb_table() {
+            let e = spectrum::rgb_to_spectrum(eta.rgb_array(), *lambda);
+            let kk = spectrum::rgb_to_spectrum(k.rgb_array(), *lambda);
+            let r = self.reflectance(cos_i, thickness, eta_i, e, kk, *lambda);
+            for c in 0..3 {
+                accum[c] += r * weight[c] / N_LAMBDAS as f64;
+            }
+        }
+        Color::new(
+            accum[0].clamp(0.0, 1.0),
+            accum[1].clamp(0.0, 1.0),
+            accum[2].clamp(0.0, 1.0),
+        )
+    }
+
+    // reflectance for the path, a single wavelength if the path carry one
+    // (spectral mode or after a dispersion), or the rgb integral
+    pub fn fresnel(
+        &self,
+        cos_i: f64,
+        rec: &HitRecord,
+        wavelength: Option<f64>,
+        eta_i: f64,
+        eta: Color,
+        k: Color,
+    ) -> Color {
+        let d = self.thickness(rec);
+        match wavelength {
+            Some(lambda) => {
+                let e = spectrum::rgb_to_spectrum(eta.rgb_array(), lambda);
+                let kk = spectrum::rgb_to_spectrum(k.rgb_array(), lambda);
+                let r = self.reflectance(cos_i, d, eta_i, e, kk, lambda);
+                Color::new(r, r, r)
+            }
+            None => self.reflectance_rgb(cos_i, d, eta_i, eta, k),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use image::RgbImage;
+
+// rectangle of pixels, from (x0, y0) to (x1, y1) without the last ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Region {
+    pub fn new(x: u32, y: u32, wid: u32, hei: u32) -> Self {
+        Region {
+            x0: x,
+            y0: y,
+            x1: x + wid,
+            y1: y + hei,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.x1.saturating_sub(self.x0)
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1.saturating_sub(self.y0)
+    }
+
+    pub fn area(&self) -> u64 {
+        self.width() as u64 * self.height() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.area() == 0
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        self.x0 <= x && x < self.x1 && self.y0 <= y && y < self.y1
+    }
+
+    // the part inside an image of the size
+    pub fn clamp(&self, wid: u32, hei: u32) -> Self {
+        Region {
+            x0: self.x0.min(wid),
+            y0: self.y0.min(hei),
+            x1: self.x1.min(wid),
+            y1: self.y1.min(hei),
+        }
+    }
+
+    // the pixels in rows
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.y0..self.y1).flat_map(move |y| (self.x0..self.x1).map(move |x| (x, y)))
+    }
+
+    // copy the pixels of the region from a render, like a crop with more
+    // samples, into other image of the same frame
+    pub fn paste(&self, from: &RgbImage, into: &mut RgbImage) {
+        let r = self
+            .clamp(from.width(), from.height())
+            .clamp(into.width(), into.height());
+        for (x, y) in r.pixels() {
+            into.put_pixel(x, y, *from.get_pixel(x, y));
+        }
+    }
+}
+
+// the order the tiles are given to the threads, the first ones are done
+// first, so the preview show them before
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    // rows from the top, left to right
+    #[default]
+    Scanline,
+    // from the center to the borders, where the subject usually is
+    Spiral,
+    // the hilbert curve, near tiles are done together, better for the caches
+    Hilbert,
+}
+
+// the region split in tiles of size x size, the ones in the borders smaller
+pub fn tiles(region: &Region, size: u32, order: TileOrder) -> Vec<Region> {
+    let size = size.max(1);
+    let cols = region.width().div_ceil(size);
+    let rows = region.height().div_ceil(size);
+    let tile = |(i, j): (u32, u32)| {
+        Region {
+            x0: region.x0 + i * size,
+            y0: region.y0 + j * size,
+            x1: region.x0 + (i + 1) * size,
+            y1: region.y0 + (j + 1) * size,
+        }
+        .clamp(region.x1, region.y1)
+    };
+    let grid = (0..rows).flat_map(|j| (0..cols).map(move |i| (i, j)));
+    match order {
+        TileOrder::Scanline => grid.map(tile).collect(),
+        TileOrder::Spiral => spiral(cols, rows).into_iter().map(tile).collect(),
+        TileOrder::Hilbert => {
+            let n = cols.max(rows).next_power_of_two();
+            let mut grid: Vec<_> = grid.collect();
+            grid.sort_by_key(|(i, j)| hilbert_index(n, *i, *j));
+            grid.into_iter().map(tile).collect()
+        }
+    }
+}
+
+// walk around the center tile, right, down, left, up, each two turns a
+// step longer, keeping the cells inside the grid
+fn spiral(cols: u32, rows: u32) -> Vec<(u32, u32)> {
+    let total = (cols * rows) as usize;
+    let mut cells = Vec::with_capacity(total);
+    let (mut x, mut y) = ((cols as i64 - 1) / 2, (rows as i64 - 1) / 2);
+    let dirs = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut len = 1;
+    let mut d = 0;
+    while cells.len() < total {
+        for _ in 0..2 {
+            let (dx, dy) = dirs[d % 4];
+            for _ in 0..len {
+                if (0..cols as i64).contains(&x) && (0..rows as i64).contains(&y) {
+                    cells.push((x as u32, y as u32));
+                }
+                x += dx;
+                y += dy;
+            }
+            d += 1;
+        }
+        len += 1;
+    }
+    cells.truncate(total);
+    cells
+}
+
+// position of the cell in the hilbert curve of a n x n grid, n power of 2
+fn hilbert_index(n: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        // rotate the quadrant
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
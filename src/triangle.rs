@@ -0,0 +1,107 @@
+use crate::{
+    aabb::Aabb,
+    material::Material,
+    ray::{HitRecord, Hittable, Ray},
+    vec::Vec3,
+};
+
+// triangle with the vertices a, b, c and a (u, v) in each vertex
+// by default the uvs are (0, 0), (1, 0) and (0, 1), like half of a Quad
+pub struct Triangle {
+    a: Vec3,
+    b: Vec3,
+    c: Vec3,
+    uvs: [(f64, f64); 3],
+    normal: Vec3,
+    mat: Box<dyn Material + Sync>,
+    bbox: Aabb,
+}
+
+impl Triangle {
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, mat: Box<dyn Material + Sync>) -> Self {
+        let bbox = Aabb::union(&Aabb::from_points(a, b), &Aabb::from_points(a, c));
+        Triangle {
+            a,
+            b,
+            c,
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            normal: ((b - a) * (c - a)).unit_vec(),
+            mat,
+            bbox,
+        }
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+
+    // dp/du and dp/dv, solving the edges by the diferences of the uvs
+    fn tangents(&self) -> (Vec3, Vec3) {
+        let [uv0, uv1, uv2] = self.uvs;
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let dp02 = self.a - self.c;
+        let dp12 = self.b - self.c;
+        let det = du02 * dv12 - dv02 * du12;
+        if det.abs() < 1e-12 {
+            // degenerate uvs, any frame along the edges
+            return (self.b - self.a, self.c - self.a);
+        }
+        let inv = 1.0 / det;
+        (
+            (dp02.mul(dv12) - dp12.mul(dv02)).mul(inv),
+            (dp12.mul(du02) - dp02.mul(du12)).mul(inv),
+        )
+    }
+}
+
+impl Hittable for Triangle {
+    // Moller-Trumbore, the barycentric coordinates without the plane
+    fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
+        let e1 = self.b - self.a;
+        let e2 = self.c - self.a;
+        let pvec = r.direction * e2;
+        let det = e1.dot(&pvec);
+        // parallel to the triangle
+        if det.abs() < 1e-12 {
+            return false;
+        }
+        let inv = 1.0 / det;
+
+        let tvec = r.origin - self.a;
+        let b1 = tvec.dot(&pvec) * inv;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+        let qvec = tvec * e1;
+        let b2 = r.direction.dot(&qvec) * inv;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+
+        let t = e2.dot(&qvec) * inv;
+        if t <= r_min || r_max <= t {
+            return false;
+        }
+
+        let b0 = 1.0 - b1 - b2;
+        let [uv0, uv1, uv2] = self.uvs;
+        rec.t = t;
+        rec.point = r.at(t);
+        rec.u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+        rec.v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+        (rec.tangent, rec.bitangent) = self.tangents();
+        rec.set_face_normal(r, self.normal);
+        rec.mat = self.mat.clone_box();
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
@@ -1,7 +1,8 @@
 #![allow(dead_code, unused)]
 use core::f64;
+use std::cell::RefCell;
 
-use rand::prelude::*;
+use rand::{prelude::*, rngs::SmallRng};
 
 use crate::vec::{Vec3, VecTypes};
 
@@ -11,10 +12,33 @@ pub fn degrees_to_radians(deg: f64) -> f64 {
     deg * f64::consts::PI / 180.0
 }
 
-pub fn randon_f64() -> f64 {
-    let mut rng = rand::rng();
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_os_rng());
+}
 
-    rng.random::<f64>()
+// splitmix64, spread near numbers to far seeds
+pub fn mix_seed(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// one seed from many numbers, like the seed of a render, the sample and
+// the pixel, each combination give a unrelated seed
+pub fn hash_seed(parts: &[u64]) -> u64 {
+    parts.iter().fold(0, |h, p| mix_seed(h ^ p))
+}
+
+// restart the random numbers of this thread from the seed, before it each
+// thread has a random one from the os
+// the render seed each sample, so it is the same in any thread
+pub fn seed_rng(seed: u64) {
+    RNG.with_borrow_mut(|rng| *rng = SmallRng::seed_from_u64(seed));
+}
+
+pub fn randon_f64() -> f64 {
+    RNG.with_borrow_mut(|rng| rng.random::<f64>())
 }
 
 // so por preguica de trocar
@@ -23,6 +47,7 @@ pub fn random_Interval_f64(min: f64, max: f64) -> f64 {
     min + randon_f64() * (max - min)
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
@@ -41,6 +66,24 @@ impl Interval {
         self.min <= x && x <= self.max
     }
 
+    // smallest interval that contains both
+    pub fn union(a: &Interval, b: &Interval) -> Self {
+        Interval {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    pub fn size(&self) -> f64 {
+        self.max - self.min
+    }
+
+    // pad the interval with delta / 2 in each side
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     pub fn clamp(&self, x: f64) -> f64 {
         if x < self.min {
             return self.min;
@@ -51,6 +94,11 @@ impl Interval {
     }
 }
 
+pub const EMPTY: Interval = Interval {
+    min: INF,
+    max: -INF,
+};
+
 impl Default for Interval {
     fn default() -> Self {
         Interval {
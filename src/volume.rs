@@ -0,0 +1,284 @@
+use std::{fs, io, path::Path};
+
+use crate::{
+    aabb::Aabb,
+    color::Color,
+    material::{Material, Volumetric},
+    medium::sample_distance,
+    perlin::Perlin,
+    phase::{Isotropic, PhaseFunction},
+    ray::{HitRecord, Hittable, Ray},
+    utils::randon_f64,
+    vec::{Vec3, VecTypes},
+};
+
+// dense voxel grid, the values are in the corners of the cells
+// and the index is x + nx * (y + ny * z)
+#[derive(Clone)]
+pub struct DensityGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f64>,
+    max: f64,
+}
+
+impl DensityGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f64>) -> io::Result<Self> {
+        let len = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz));
+        if nx < 2 || ny < 2 || nz < 2 || len != Some(data.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "grid need at least 2 values by axis and nx * ny * nz values",
+            ));
+        }
+        let max = data.iter().cloned().fold(0.0, f64::max);
+        Ok(DensityGrid {
+            nx,
+            ny,
+            nz,
+            data,
+            max,
+        })
+    }
+
+    // text format, the first 3 numbers are nx ny nz, then all the values
+    // separated by whitespace with x changing faster, z slower
+    pub fn load_text<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut nums = text.split_whitespace();
+        let mut dim = || -> io::Result<usize> {
+            nums.next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad grid header"))
+        };
+        let (nx, ny, nz) = (dim()?, dim()?, dim()?);
+        let data = nums
+            .map(|n| n.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Self::new(nx, ny, nz, data)
+    }
+
+    // raw format, only little endian f32 values in the same order of text
+    // the dimensions are not in the file
+    pub fn load_raw<P: AsRef<Path>>(path: P, nx: usize, ny: usize, nz: usize) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let data = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        Self::new(nx, ny, nz, data)
+    }
+
+    // procedural cloud, turbulence of Perlin noise that fade to zero
+    // near the border of the grid, scale is the frequency of the noise
+    pub fn from_perlin(n: usize, scale: f64) -> Self {
+        let n = n.max(2);
+        let noise = Perlin::new();
+        let mut data = Vec::with_capacity(n * n * n);
+        for k in 0..n {
+            for j in 0..n {
+                for i in 0..n {
+                    let p = Vec3::new(
+                        VecTypes::Coordinates,
+                        i as f64 / (n - 1) as f64,
+                        j as f64 / (n - 1) as f64,
+                        k as f64 / (n - 1) as f64,
+                    );
+                    // distance to center, 1.0 in the middle of the faces
+                    let d = p.sum(-0.5).vec_length() * 2.0;
+                    let falloff = (1.0 - d).max(0.0);
+                    let v = noise.turb(&p.mul(scale), 7) * 2.0 * falloff;
+                    data.push(v.min(1.0));
+                }
+            }
+        }
+        Self::new(n, n, n, data).unwrap()
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    fn at(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.data[i + self.nx * (j + self.ny * k)]
+    }
+
+    // trilinear interpolation, p in [0.0, 1.0] in all axis
+    pub fn lookup(&self, p: &Vec3) -> f64 {
+        let dims = [self.nx, self.ny, self.nz];
+        let mut idx = [0usize; 3];
+        let mut frac = [0.0; 3];
+        for a in 0..3 {
+            let x = p[a].clamp(0.0, 1.0) * (dims[a] - 1) as f64;
+            let i = (x.floor() as usize).min(dims[a] - 2);
+            idx[a] = i;
+            frac[a] = x - i as f64;
+        }
+
+        let mut accum = 0.0;
+        for dk in 0..2 {
+            for dj in 0..2 {
+                for di in 0..2 {
+                    let w = (if di == 1 { frac[0] } else { 1.0 - frac[0] })
+                        * (if dj == 1 { frac[1] } else { 1.0 - frac[1] })
+                        * (if dk == 1 { frac[2] } else { 1.0 - frac[2] });
+                    accum += w * self.at(idx[0] + di, idx[1] + dj, idx[2] + dk);
+                }
+            }
+        }
+        accum
+    }
+}
+
+// the material in the point of collision of the volume
+// the phase function of the volume more the emission of that point
+struct VolumeEvent {
+    phase: Box<dyn Material>,
+    emission: Color,
+}
+
+impl Material for VolumeEvent {
+    fn clone_box(&self) -> Box<dyn Material> {
+        Box::new(VolumeEvent {
+            phase: self.phase.clone_box(),
+            emission: self.emission,
+        })
+    }
+    fn reflect(
+        &self,
+        r_in: &Ray,
+        r_ref: &mut Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+    ) -> bool {
+        self.phase.reflect(r_in, r_ref, rec, attenuation)
+    }
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        self.emission
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> Color {
+        self.phase.eval(r_in, rec, wi)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Vec3) -> f64 {
+        self.phase.pdf(r_in, rec, wi)
+    }
+    fn is_medium(&self) -> bool {
+        true
+    }
+}
+
+// volume with density from a grid, placed in the box bbox
+// use delta tracking: walk with the majorant(max density) and accept
+// the collision with probability density / majorant, unbiased for any grid
+pub struct GridMedium {
+    grid: DensityGrid,
+    bbox: Aabb,
+    density_scale: f64,
+    phase_function: Volumetric,
+    albedo: Color,
+    // radiance emitted by the absorption, grid * color, for fire
+    emission: Option<(DensityGrid, Color)>,
+}
+
+impl GridMedium {
+    pub fn new(grid: DensityGrid, bbox: Aabb, density_scale: f64, albedo: Color) -> Self {
+        Self::new_with_phase(grid, bbox, density_scale, albedo, Box::new(Isotropic {}))
+    }
+
+    pub fn new_with_phase(
+        grid: DensityGrid,
+        bbox: Aabb,
+        density_scale: f64,
+        albedo: Color,
+        phase: Box<dyn PhaseFunction>,
+    ) -> Self {
+        GridMedium {
+            grid,
+            bbox,
+            density_scale,
+            phase_function: Volumetric::new(albedo, phase),
+            albedo,
+            emission: None,
+        }
+    }
+
+    pub fn with_emission(mut self, grid: DensityGrid, color: Color) -> Self {
+        self.emission = Some((grid, color));
+        self
+    }
+
+    pub fn boxed(self) -> Box<Self> {
+        Box::new(self)
+    }
+
+    // point in world to [0.0, 1.0] of the grid
+    fn grid_coord(&self, p: &Vec3) -> Vec3 {
+        let min = self.bbox.min();
+        let size = self.bbox.max() - min;
+        Vec3::new(
+            VecTypes::Coordinates,
+            (p[0] - min[0]) / size[0],
+            (p[1] - min[1]) / size[1],
+            (p[2] - min[2]) / size[2],
+        )
+    }
+
+    fn density(&self, p: &Vec3) -> f64 {
+        self.grid.lookup(&self.grid_coord(p)) * self.density_scale
+    }
+
+    fn majorant(&self) -> f64 {
+        self.grid.max() * self.density_scale
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, r: &Ray, r_max: f64, r_min: f64, rec: &mut HitRecord) -> bool {
+        // box clipped to the ray segment, so a ray starting inside is ok
+        let Some(inside) = self.bbox.hit_interval(r, r_max, r_min) else {
+            return false;
+        };
+        let majorant = self.majorant();
+        if majorant <= 0.0 {
+            return false;
+        }
+
+        let ray_len = r.direction.vec_length();
+        let mut t = inside.min;
+        loop {
+            t += sample_distance(majorant) / ray_len;
+            if t >= inside.max {
+                return false;
+            }
+            let p = r.at(t);
+            // real collision, else is a null collision and keep walking
+            if randon_f64() < self.density(&p) / majorant {
+                rec.t = t;
+                rec.point = p;
+                rec.normal = Vec3::new(VecTypes::Coordinates, 1.0, 0.0, 0.0);
+                rec.front_face = true;
+                // the collision is an absorption with sigma_a / sigma_t
+                // (1 - albedo), only there the medium emit
+                let emission = match &self.emission {
+                    Some((g, c)) => {
+                        let a = self.albedo.rgb_array();
+                        let absorption = Color::new(1.0 - a[0], 1.0 - a[1], 1.0 - a[2]);
+                        c.mul(g.lookup(&self.grid_coord(&p))) * absorption
+                    }
+                    None => Color::default(),
+                };
+                rec.mat = Box::new(VolumeEvent {
+                    phase: self.phase_function.clone_box(),
+                    emission,
+                });
+                return true;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}